@@ -0,0 +1,87 @@
+//! Compares `lazy_replace_static`, which looks up a cached set or, for a few needles, compares
+//! them directly, against rendering with a `StaticNeedleSet` whose trie is built at compile time.
+//! Run with `cargo bench`.
+//!
+//! There is no `lazy_replace_any` that builds a matcher for every call, so the per-call baseline is
+//! "rebuilt", which builds the `StaticNeedleSet` trie again with `StaticNeedleSet::new` for every
+//! render. "few direct" is the direct comparison of up to three needles, with "few static" as the
+//! same needles in a compile-time set.
+
+use lazy_string_replace::{needle_set, LazyReplace, StaticNeedleSet};
+use std::{
+    fmt::Write,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const NEEDLES: &[&str] = &["alpha", "beta", "gamma", "delta", "epsilon"];
+const FEW_NEEDLES: &[&str] = &["alpha", "beta", "gamma"];
+static SET: StaticNeedleSet = needle_set!["alpha", "beta", "gamma", "delta", "epsilon"];
+static FEW_SET: StaticNeedleSet = needle_set!["alpha", "beta", "gamma"];
+
+const ITERATIONS: u32 = 20_000;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<12} {:>10.2?}/iter",
+        name,
+        Duration::from_nanos((elapsed.as_nanos() / u128::from(ITERATIONS)) as u64)
+    );
+}
+
+fn main() {
+    let haystack = "the alpha and the omega, beta testing, gamma rays and delta waves ".repeat(8);
+    let mut out = String::with_capacity(haystack.len());
+
+    bench("static", || {
+        out.clear();
+        write!(out, "{}", black_box(&haystack).lazy_replace_set(&SET, "_")).unwrap();
+        black_box(&out);
+    });
+
+    bench("rebuilt", || {
+        let set = StaticNeedleSet::new(black_box(NEEDLES));
+        out.clear();
+        write!(out, "{}", black_box(&haystack).lazy_replace_set(&set, "_")).unwrap();
+        black_box(&out);
+    });
+
+    bench("cached", || {
+        out.clear();
+        write!(
+            out,
+            "{}",
+            black_box(&haystack).lazy_replace_static(NEEDLES, "_")
+        )
+        .unwrap();
+        black_box(&out);
+    });
+
+    bench("few static", || {
+        out.clear();
+        write!(
+            out,
+            "{}",
+            black_box(&haystack).lazy_replace_set(&FEW_SET, "_")
+        )
+        .unwrap();
+        black_box(&out);
+    });
+
+    bench("few direct", || {
+        out.clear();
+        write!(
+            out,
+            "{}",
+            black_box(&haystack).lazy_replace_static(FEW_NEEDLES, "_")
+        )
+        .unwrap();
+        black_box(&out);
+    });
+}
@@ -0,0 +1,212 @@
+//! Streaming replacement into a `tokio::io::AsyncWrite`. Requires the `tokio` feature.
+
+use crate::stream::{Piece, StreamMatcher};
+use std::{
+    convert::Infallible,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::AsyncWrite;
+
+/// An `AsyncWrite` adapter that replaces each occurrence of a byte needle in everything written
+/// through it, before passing it on to the inner writer.
+///
+/// Matches may be split across any number of `poll_write` calls. The end of what was written so far
+/// is held back for as long as it could still be the start of a match, so it is only passed on
+/// once a later write settles it, or when the adapter is shut down. `poll_flush` flushes everything
+/// else, but can't pass those bytes on, since the next write may still complete the match.
+///
+/// An empty needle never matches.
+pub struct ReplaceAsyncWriter<'a, W, R> {
+    writer: W,
+    needle: &'a [u8],
+    replacement: R,
+    matcher: StreamMatcher,
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<'a, W, R> ReplaceAsyncWriter<'a, W, R> {
+    /// Create a new instance of this type
+    pub fn new(writer: W, needle: &'a [u8], replacement: R) -> Self {
+        ReplaceAsyncWriter {
+            writer,
+            needle,
+            replacement,
+            matcher: StreamMatcher::default(),
+            pending: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Get a reference to the inner writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Unwrap this adapter, returning the inner writer. Anything that hasn't been passed on to it
+    /// yet is lost, so shut this adapter down first.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<'a, W, R> ReplaceAsyncWriter<'a, W, R>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Pass everything that has been replaced already on to the inner writer
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let n =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.written..]))?;
+
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+
+            self.written += n;
+        }
+
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn push_piece(
+    pending: &mut Vec<u8>,
+    replacement: &[u8],
+    piece: Piece<'_>,
+) -> Result<(), Infallible> {
+    match piece {
+        Piece::Literal(bytes) => pending.extend_from_slice(bytes),
+        Piece::Match => pending.extend_from_slice(replacement),
+    }
+
+    Ok(())
+}
+
+impl<'a, W, R> AsyncWrite for ReplaceAsyncWriter<'a, W, R>
+where
+    W: AsyncWrite + Unpin,
+    R: AsRef<[u8]> + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_drain(cx))?;
+
+        let (pending, replacement) = (&mut this.pending, this.replacement.as_ref());
+        let Ok(()) = this.matcher.feed(this.needle, buf, |piece| {
+            push_piece(pending, replacement, piece)
+        });
+
+        // the bytes are accepted now, and passed on by the next call
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let (pending, replacement) = (&mut this.pending, this.replacement.as_ref());
+        let Ok(()) = this
+            .matcher
+            .finish(this.needle, |piece| push_piece(pending, replacement, piece));
+
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplaceAsyncWriter;
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+    use tokio::io::AsyncWrite;
+
+    /// A writer that accepts one byte at a time, and only on every other call
+    #[derive(Default)]
+    struct Trickle {
+        out: Vec<u8>,
+        ready: bool,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if self.ready {
+                self.out.push(buf[0]);
+                Poll::Ready(Ok(1))
+            } else {
+                Poll::Pending
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn write_chunked<W>(writer: W, needle: &str, replacement: &str, chunks: &[&str]) -> W
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut adapter = ReplaceAsyncWriter::new(writer, needle.as_bytes(), replacement);
+
+        for chunk in chunks {
+            let mut chunk = chunk.as_bytes();
+            while !chunk.is_empty() {
+                if let Poll::Ready(n) = Pin::new(&mut adapter).poll_write(&mut cx, chunk) {
+                    chunk = &chunk[n.unwrap()..];
+                }
+            }
+        }
+        while Pin::new(&mut adapter).poll_shutdown(&mut cx).is_pending() {}
+
+        adapter.into_inner()
+    }
+
+    #[test]
+    fn replace_across_writes() {
+        let out = write_chunked(Vec::new(), "cat", "dog", &["a c", "at and a ca", "t, c"]);
+        assert_eq!(b"a dog and a dog, c", &out[..]);
+
+        let out = write_chunked(Vec::new(), "abab", "_", &["ab", "ab", "ab"]);
+        assert_eq!(b"_ab", &out[..]);
+
+        let out = write_chunked(Vec::new(), "", "_", &["un", "changed"]);
+        assert_eq!(b"unchanged", &out[..]);
+    }
+
+    #[test]
+    fn replace_into_slow_writer() {
+        let out = write_chunked(Trickle::default(), "cat", "dog", &["a c", "at, c", "a"]);
+        assert_eq!(b"a dog, ca", &out.out[..]);
+    }
+}
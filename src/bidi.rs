@@ -0,0 +1,47 @@
+//! Reordering rendered text for display, using the Unicode Bidirectional Algorithm. Requires the
+//! `bidi` feature, which pulls in the `unicode-bidi` crate.
+
+use std::fmt;
+use unicode_bidi::BidiInfo;
+
+/// See [`LazyReplaceDisplay::render_bidi`](crate::LazyReplaceDisplay::render_bidi)
+pub(crate) fn render<D>(inner: &D) -> String
+where
+    D: fmt::Display + ?Sized,
+{
+    let logical = inner.to_string();
+    let info = BidiInfo::new(&logical, None);
+    let mut visual = String::with_capacity(logical.len());
+
+    for paragraph in &info.paragraphs {
+        let text = &logical[paragraph.range.clone()];
+        let line_len = text.trim_end_matches(['\r', '\n']).len();
+        let line = paragraph.range.start..paragraph.range.start + line_len;
+
+        visual.push_str(&info.reorder_line(paragraph, line));
+        visual.push_str(&text[line_len..]);
+    }
+
+    visual
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LazyReplace, LazyReplaceDisplay};
+
+    #[test]
+    fn render_bidi() {
+        assert_eq!(
+            "say \u{5DD}\u{5D5}\u{5DC}\u{5E9} twice",
+            "say \u{5E9}\u{5DC}\u{5D5}\u{5DD} twice".render_bidi()
+        );
+        // the match is in the right-to-left run, and its replacement is reordered along with it
+        assert_eq!(
+            "say \u{5DD}\u{5DC}\u{5D5}\u{5E2} twice\nok",
+            "say PLACEHOLDER twice\nok"
+                .lazy_replace("PLACEHOLDER", "\u{5E2}\u{5D5}\u{5DC}\u{5DD}")
+                .render_bidi()
+        );
+        assert_eq!("plain\n", "plain\n".render_bidi());
+    }
+}
@@ -0,0 +1,854 @@
+//! Replacements that only apply to some matches, depending on their surroundings.
+
+use crate::{
+    pattern::{Pattern, ReverseSearcher, Searcher},
+    write_matches, write_replacement, ReplacedString,
+};
+use std::{
+    fmt::{self, Write},
+    ops::Range,
+};
+
+/// A lazily-replaced string that is replaced as a whole, but only if it is exactly equal to the
+/// needle. Unlike a normal replacement, a haystack that merely contains the needle is left as it
+/// is.
+pub struct ReplacedExact<'a, 'n, R> {
+    haystack: &'a str,
+    needle: &'n str,
+    replacement: R,
+}
+
+impl<'a, 'n, R> ReplacedExact<'a, 'n, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: &'n str, replacement: R) -> Self {
+        ReplacedExact {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, 'n, R> fmt::Display for ReplacedExact<'a, 'n, R>
+where
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.haystack == self.needle {
+            write_replacement(f, &self.replacement)
+        } else {
+            f.write_str(self.haystack)
+        }
+    }
+}
+
+/// A lazily-replaced string where a match is only replaced if it is a whole token, meaning that
+/// the characters on either side of it are delimiters, or that it is at the start or end of the
+/// haystack. Other matches are left as they are.
+pub struct ReplacedToken<'a, 'd, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+    delims: &'d [char],
+}
+
+impl<'a, 'd, P, R> ReplacedToken<'a, 'd, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R, delims: &'d [char]) -> Self {
+        ReplacedToken {
+            haystack,
+            needle,
+            replacement,
+            delims,
+        }
+    }
+}
+
+impl<'a, 'd, P, R> fmt::Display for ReplacedToken<'a, 'd, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_bounded(
+            f,
+            self.haystack,
+            self.needle.clone(),
+            &self.replacement,
+            |c| self.delims.contains(&c),
+        )
+    }
+}
+
+/// A lazily-replaced string where a match is only replaced if it is a whole identifier, as in most
+/// programming languages: it has to start with a letter or `_` and consist of only letters, digits
+/// and `_`, and the characters on either side of it, if any, have to be something else. Letters and
+/// digits are classified by [`char::is_alphabetic`] and [`char::is_alphanumeric`], so non-ASCII
+/// identifiers count too. Other matches are left as they are.
+pub struct ReplacedIdentifier<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedIdentifier<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R) -> Self {
+        ReplacedIdentifier {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedIdentifier<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            let matched = &self.haystack[range.clone()];
+            let is_identifier = matched
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+                && matched.chars().all(is_word_char);
+
+            if is_identifier && is_bounded(self.haystack, range, |c| !is_word_char(c)) {
+                write_replacement(f, &self.replacement)
+            } else {
+                f.write_str(matched)
+            }
+        })
+    }
+}
+
+/// A lazily-replaced string where a match is only replaced if it isn't inside parentheses. Text
+/// inside parentheses, including matches, is left as it is.
+///
+/// The depth is decided by where a match starts. Unbalanced closing parentheses are ignored, so
+/// text after an excess `)` counts as being outside of all parentheses again.
+pub struct ReplacedOutsideParens<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedOutsideParens<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R) -> Self {
+        ReplacedOutsideParens {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedOutsideParens<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut depth = ParenDepth::default();
+        let mut position = 0;
+
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            depth.advance(&self.haystack[position..range.start]);
+            let outside = depth.0 == 0;
+            depth.advance(&self.haystack[range.clone()]);
+            position = range.end;
+
+            if outside {
+                write_replacement(f, &self.replacement)
+            } else {
+                f.write_str(&self.haystack[range])
+            }
+        })
+    }
+}
+
+/// The number of parentheses open at some point in a string
+#[derive(Default)]
+struct ParenDepth(usize);
+
+impl ParenDepth {
+    fn advance(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '(' => self.0 += 1,
+                ')' => self.0 = self.0.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A lazily-replaced string where each run of consecutive characters matching a predicate is
+/// replaced as a whole, but only if it is at least `min_len` characters long. Shorter runs are
+/// left as they are.
+///
+/// By default, a run that ends at the very end of the string is complete, and so it is judged by
+/// its length like any other run. When the string is only a piece of a longer text, such a run may
+/// really be the start of a longer one, which
+/// [`treat_trailing_partial_as_match`](ReplacedMinRun::treat_trailing_partial_as_match) can account
+/// for.
+pub struct ReplacedMinRun<'a, F, R> {
+    haystack: &'a str,
+    predicate: F,
+    replacement: R,
+    min_len: usize,
+    trailing_partial_as_match: bool,
+}
+
+impl<'a, F, R> ReplacedMinRun<'a, F, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, predicate: F, replacement: R, min_len: usize) -> Self {
+        ReplacedMinRun {
+            haystack,
+            predicate,
+            replacement,
+            min_len,
+            trailing_partial_as_match: false,
+        }
+    }
+
+    /// Set whether a run at the very end of the string that is shorter than `min_len`, but not
+    /// empty, is replaced anyway, as if it continued past the end. This is `false` by default.
+    pub fn treat_trailing_partial_as_match(mut self, yes: bool) -> Self {
+        self.trailing_partial_as_match = yes;
+        self
+    }
+}
+
+impl<'a, F, R> fmt::Display for ReplacedMinRun<'a, F, R>
+where
+    F: Fn(char) -> bool,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.haystack;
+
+        while let Some(run_start) = rest.find(&self.predicate) {
+            f.write_str(&rest[..run_start])?;
+            rest = &rest[run_start..];
+
+            let run_len = rest.find(|c| !(self.predicate)(c)).unwrap_or(rest.len());
+            let run = &rest[..run_len];
+
+            let is_trailing = run_len == rest.len();
+
+            if run.chars().count() >= self.min_len
+                || (is_trailing && self.trailing_partial_as_match)
+            {
+                write_replacement(f, &self.replacement)?;
+            } else {
+                f.write_str(run)?;
+            }
+
+            rest = &rest[run_len..];
+        }
+
+        f.write_str(rest)
+    }
+}
+
+/// A lazily-replaced string where the replacement only happens inside fenced code blocks of a
+/// Markdown document. Prose and the fence lines themselves are left as they are.
+///
+/// A line starting with three backticks, after any indentation, opens a block, and the next such
+/// line closes it. A block that is never closed extends to the end of the document, as in
+/// CommonMark. Inline code spans and `~~~` fences don't count as code blocks.
+pub struct ReplacedInCodeFences<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedInCodeFences<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R) -> Self {
+        ReplacedInCodeFences {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedInCodeFences<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_block = |f: &mut fmt::Formatter, block: &'a str| {
+            fmt::Display::fmt(
+                &ReplacedString::new(block, self.needle.clone(), &self.replacement),
+                f,
+            )
+        };
+
+        let mut block_start = None;
+        let mut verbatim_start = 0;
+        let mut position = 0;
+
+        for line in self.haystack.split_inclusive('\n') {
+            let line_start = position;
+            position += line.len();
+
+            if !line.trim_start().starts_with("```") {
+                continue;
+            }
+
+            match block_start {
+                None => {
+                    f.write_str(&self.haystack[verbatim_start..position])?;
+                    block_start = Some(position);
+                }
+                Some(start) => {
+                    write_block(f, &self.haystack[start..line_start])?;
+                    verbatim_start = line_start;
+                    block_start = None;
+                }
+            }
+        }
+
+        match block_start {
+            Some(start) => write_block(f, &self.haystack[start..]),
+            None => f.write_str(&self.haystack[verbatim_start..]),
+        }
+    }
+}
+
+/// A lazily-replaced string where the replacement happens everywhere except inside the spans
+/// matched by a second, `skip` pattern, which are left as they are.
+///
+/// The skip spans are found first, and the needle is only searched for in the text between them,
+/// so an occurrence of the needle that overlaps a skip span, even partially, isn't replaced. An
+/// occurrence that contains a whole skip span isn't replaced either, since it is split up by it.
+pub struct ReplacedExcept<'a, P, S, R> {
+    haystack: &'a str,
+    needle: P,
+    skip: S,
+    replacement: R,
+}
+
+impl<'a, P, S, R> ReplacedExcept<'a, P, S, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, skip: S, replacement: R) -> Self {
+        ReplacedExcept {
+            haystack,
+            needle,
+            skip,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, S, R> fmt::Display for ReplacedExcept<'a, P, S, R>
+where
+    P: Pattern<'a> + Clone,
+    S: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_gap = |f: &mut fmt::Formatter, gap: &'a str| {
+            fmt::Display::fmt(
+                &ReplacedString::new(gap, self.needle.clone(), &self.replacement),
+                f,
+            )
+        };
+
+        let mut skips = self.skip.clone().into_searcher(self.haystack);
+        let mut position = 0;
+
+        while let Some((start, end)) = skips.next_match() {
+            write_gap(f, &self.haystack[position..start])?;
+            f.write_str(&self.haystack[start..end])?;
+            position = end;
+        }
+
+        write_gap(f, &self.haystack[position..])
+    }
+}
+
+/// A lazily-replaced string where each match is replaced with a word that is kept apart from the
+/// words around it by spaces.
+///
+/// A space is written in front of the replacement if the character right before the match is a
+/// word character, that is alphanumeric or `_`, and behind it if the character right after the
+/// match is one. Next to whitespace, punctuation or either end of the haystack, no space is added.
+/// Only the haystack is looked at, so a replacement that starts or ends with a space of its own
+/// still gets another one, and the neighbour of a match may be part of an adjacent match.
+pub struct ReplacedSpacedWord<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedSpacedWord<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R) -> Self {
+        ReplacedSpacedWord {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedSpacedWord<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            if self.haystack[..range.start]
+                .chars()
+                .next_back()
+                .is_some_and(is_word_char)
+            {
+                f.write_char(' ')?;
+            }
+            write_replacement(f, &self.replacement)?;
+            if self.haystack[range.end..]
+                .chars()
+                .next()
+                .is_some_and(is_word_char)
+            {
+                f.write_char(' ')?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// A lazily-replaced string where only the last match of the needle is replaced, like
+/// [`str::rfind`] would find it.
+///
+/// The match is searched for from the end of the haystack, so where occurrences overlap, the one
+/// that ends last is replaced: in `"aaa"`, the last match of `"aa"` is the one starting at index 1,
+/// not the one a forward search would find at index 0.
+pub struct ReplacedLast<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedLast<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R) -> Self {
+        ReplacedLast {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedLast<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    P::Searcher: ReverseSearcher<'a>,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self
+            .needle
+            .clone()
+            .into_searcher(self.haystack)
+            .next_match_back()
+        {
+            Some((start, end)) => {
+                f.write_str(&self.haystack[..start])?;
+                write_replacement(f, &self.replacement)?;
+                f.write_str(&self.haystack[end..])
+            }
+            None => f.write_str(self.haystack),
+        }
+    }
+}
+
+/// A lazily-replaced string where the span from the first match of a start pattern up to the last
+/// match of an end pattern after it is replaced as a whole, markers included. The text outside that
+/// span is left as it is.
+///
+/// The end pattern is only searched for after the end of the first start match. If either pattern
+/// doesn't match, the string is displayed unchanged.
+pub struct ReplacedSpan<'a, S, E, R> {
+    haystack: &'a str,
+    start: S,
+    end: E,
+    replacement: R,
+}
+
+impl<'a, S, E, R> ReplacedSpan<'a, S, E, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, start: S, end: E, replacement: R) -> Self {
+        ReplacedSpan {
+            haystack,
+            start,
+            end,
+            replacement,
+        }
+    }
+}
+
+impl<'a, S, E, R> fmt::Display for ReplacedSpan<'a, S, E, R>
+where
+    S: Pattern<'a> + Clone,
+    E: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self
+            .start
+            .clone()
+            .into_searcher(self.haystack)
+            .next_match()
+            .and_then(|(start, after_start)| {
+                let mut ends = self
+                    .end
+                    .clone()
+                    .into_searcher(&self.haystack[after_start..]);
+                let (_, end) = std::iter::from_fn(|| ends.next_match()).last()?;
+
+                Some(start..after_start + end)
+            });
+
+        match span {
+            Some(span) => {
+                f.write_str(&self.haystack[..span.start])?;
+                write_replacement(f, &self.replacement)?;
+                f.write_str(&self.haystack[span.end..])
+            }
+            None => f.write_str(self.haystack),
+        }
+    }
+}
+
+/// Whether `c` can be part of a word or identifier, meaning that it is alphanumeric or `_`
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `haystack[range]` has a boundary, as decided by `is_boundary`, or the end of `haystack`
+/// on either side
+fn is_bounded(haystack: &str, range: Range<usize>, is_boundary: impl Fn(char) -> bool) -> bool {
+    let before = haystack[..range.start].chars().next_back();
+    let after = haystack[range.end..].chars().next();
+
+    before.is_none_or(&is_boundary) && after.is_none_or(&is_boundary)
+}
+
+/// Replace the matches of `pat` in `haystack` that are bounded on both sides, as decided by
+/// `is_boundary`, and write the other ones as they are
+fn write_bounded<'a, P, R>(
+    f: &mut fmt::Formatter,
+    haystack: &'a str,
+    pat: P,
+    replacement: R,
+    is_boundary: impl Fn(char) -> bool,
+) -> fmt::Result
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    write_matches(f, haystack, pat, |f, _, range| {
+        if is_bounded(haystack, range.clone(), &is_boundary) {
+            write_replacement(f, &replacement)
+        } else {
+            f.write_str(&haystack[range])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LazyReplace;
+
+    #[test]
+    fn replace_exact() {
+        assert_eq!("yes", "true".lazy_replace_exact("true", "yes").to_string());
+        assert_eq!(
+            "true!",
+            "true!".lazy_replace_exact("true", "yes").to_string()
+        );
+        assert_eq!(
+            "false",
+            "false".lazy_replace_exact("true", "yes").to_string()
+        );
+        assert_eq!("empty", "".lazy_replace_exact("", "empty").to_string());
+    }
+
+    #[test]
+    fn replace_token() {
+        let delims = ['.', '/'];
+
+        assert_eq!(
+            "crate/new/src.new",
+            "crate/old/src.old"
+                .lazy_replace_token("old", "new", &delims)
+                .to_string()
+        );
+        assert_eq!(
+            "new/bold/old_name/new",
+            "old/bold/old_name/old"
+                .lazy_replace_token("old", "new", &delims)
+                .to_string()
+        );
+        assert_eq!(
+            "(a b)",
+            "(a b)".lazy_replace_token("a b", "c", &delims).to_string()
+        );
+        assert_eq!("c", "a b".lazy_replace_token("a b", "c", &[]).to_string());
+    }
+
+    #[test]
+    fn replace_identifiers() {
+        assert_eq!(
+            "let total = total_count + total;",
+            "let sum = total_count + sum;"
+                .lazy_replace_identifiers("sum", "total")
+                .to_string()
+        );
+        assert_eq!(
+            "y+y*(y-1)/x_x[y]",
+            "x+x*(x-1)/x_x[x]"
+                .lazy_replace_identifiers('x', 'y')
+                .to_string()
+        );
+        assert_eq!(
+            "j;xi;ix;i2;2i;éi;j",
+            "i;xi;ix;i2;2i;éi;i"
+                .lazy_replace_identifiers("i", "j")
+                .to_string()
+        );
+        // not an identifier, even though it is bounded
+        assert_eq!(
+            "1x + 1x",
+            "1x + 1x".lazy_replace_identifiers("1x", "y").to_string()
+        );
+    }
+
+    #[test]
+    fn replace_outside_parens() {
+        assert_eq!(
+            "y + f(x, (x)) * y",
+            "x + f(x, (x)) * x"
+                .lazy_replace_outside_parens('x', 'y')
+                .to_string()
+        );
+        assert_eq!(
+            "y) y (x",
+            "x) x (x".lazy_replace_outside_parens('x', 'y').to_string()
+        );
+        assert_eq!(
+            "[] x",
+            "(a) x".lazy_replace_outside_parens("(a)", "[]").to_string()
+        );
+    }
+
+    #[test]
+    fn replace_min_run() {
+        let text = "a b  c   d    e";
+
+        assert_eq!(
+            "a b  c\td\te",
+            text.lazy_replace_min_run(|c| c == ' ', '\t', 3).to_string()
+        );
+        assert_eq!(
+            "a_b_c_d_e",
+            text.lazy_replace_min_run(|c| c == ' ', '_', 1).to_string()
+        );
+        assert_eq!(
+            text,
+            text.lazy_replace_min_run(|c| c == ' ', '_', 5).to_string()
+        );
+        assert_eq!(
+            "x- xüü",
+            "xüüü xüü"
+                .lazy_replace_min_run(|c| c == 'ü', '-', 3)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_min_run_at_the_end() {
+        let is_space = |c| c == ' ';
+
+        // a run that ends exactly at the end is complete, and replaced if it is long enough
+        assert_eq!(
+            "a\t",
+            "a   ".lazy_replace_min_run(is_space, '\t', 3).to_string()
+        );
+        assert_eq!(
+            "a  ",
+            "a  ".lazy_replace_min_run(is_space, '\t', 3).to_string()
+        );
+        assert_eq!(
+            "a\t",
+            "a  "
+                .lazy_replace_min_run(is_space, '\t', 3)
+                .treat_trailing_partial_as_match(true)
+                .to_string()
+        );
+        assert_eq!(
+            "a  b\t",
+            "a  b "
+                .lazy_replace_min_run(is_space, '\t', 3)
+                .treat_trailing_partial_as_match(true)
+                .to_string()
+        );
+        assert_eq!(
+            "a  b",
+            "a  b"
+                .lazy_replace_min_run(is_space, '\t', 3)
+                .treat_trailing_partial_as_match(true)
+                .to_string()
+        );
+        assert_eq!(
+            "a\t",
+            "a    "
+                .lazy_replace_min_run(is_space, '\t', 3)
+                .treat_trailing_partial_as_match(false)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_in_code_fences() {
+        let doc = "Call `foo()` like this:\n\n```rust\nfoo();\nfoo();\n```\n\nfoo is great.\n  ```\nfoo\n  ```\n";
+
+        assert_eq!(
+            "Call `foo()` like this:\n\n```rust\nbar();\nbar();\n```\n\nfoo is great.\n  ```\nbar\n  ```\n",
+            doc.lazy_replace_in_code_fences("foo", "bar").to_string()
+        );
+        assert_eq!(
+            "foo\n```foo\nbar\nbar",
+            "foo\n```foo\nfoo\nfoo"
+                .lazy_replace_in_code_fences("foo", "bar")
+                .to_string()
+        );
+        assert_eq!(
+            "no fences, foo",
+            "no fences, foo"
+                .lazy_replace_in_code_fences("foo", "bar")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_except() {
+        assert_eq!(
+            "x = value; y = \"keep\"; z = value",
+            "x = old; y = \"keep\"; z = old"
+                .lazy_replace_except("old", "value", "\"keep\"")
+                .to_string()
+        );
+        assert_eq!(
+            "a_b_`a b`_a_b",
+            "a b `a b` a b"
+                .lazy_replace_except(' ', '_', "`a b`")
+                .to_string()
+        );
+        // the needle overlaps the skip span, so it is left alone
+        assert_eq!(
+            "foobar",
+            "foobar".lazy_replace_except("ob", "_", "bar").to_string()
+        );
+        assert_eq!("oo_", "ooo".lazy_replace_except('o', '_', "oo").to_string());
+    }
+
+    #[test]
+    fn replace_span() {
+        assert_eq!(
+            "keep [...] keep",
+            "keep <!-- a --> b <!-- c --> keep"
+                .lazy_replace_span("<!--", "-->", "[...]")
+                .to_string()
+        );
+        assert_eq!(
+            "a_b",
+            "a<x>y<x>z>b".lazy_replace_span('<', '>', '_').to_string()
+        );
+        // the end marker has to come after the start marker
+        assert_eq!(
+            "x) _",
+            "x) (y) (z)".lazy_replace_span('(', ')', '_').to_string()
+        );
+        assert_eq!(
+            "(no end",
+            "(no end".lazy_replace_span('(', ')', '_').to_string()
+        );
+        assert_eq!(
+            "no start)",
+            "no start)".lazy_replace_span('(', ')', '_').to_string()
+        );
+        assert_eq!("_", "()".lazy_replace_span('(', ')', '_').to_string());
+    }
+
+    #[test]
+    fn replace_last() {
+        assert_eq!("a.b.c_d", "a.b.c.d".lazy_replace_last('.', '_').to_string());
+        assert_eq!(
+            "one two three four",
+            "one two three 4".lazy_replace_last("4", "four").to_string()
+        );
+        assert_eq!("a_", "aaa".lazy_replace_last("aa", '_').to_string());
+        assert_eq!(
+            "no match",
+            "no match".lazy_replace_last('.', '_').to_string()
+        );
+        assert_eq!(
+            "path/to/file_txt",
+            "path/to/file.txt"
+                .lazy_replace_last(|c: char| c == '.' || c == '/', '_')
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_spaced_word() {
+        assert_eq!(
+            "the quick fox",
+            "the_fox".lazy_replace_spaced_word('_', "quick").to_string()
+        );
+        assert_eq!(
+            "one two three",
+            "one{}three"
+                .lazy_replace_spaced_word("{}", "two")
+                .to_string()
+        );
+        assert_eq!(
+            "one two three",
+            "one {} three"
+                .lazy_replace_spaced_word("{}", "two")
+                .to_string()
+        );
+        assert_eq!(
+            "(two), two!",
+            "({}), {}!"
+                .lazy_replace_spaced_word("{}", "two")
+                .to_string()
+        );
+        assert_eq!(
+            "two 1",
+            "{}1".lazy_replace_spaced_word("{}", "two").to_string()
+        );
+        assert_eq!(
+            "two",
+            "{}".lazy_replace_spaced_word("{}", "two").to_string()
+        );
+        assert_eq!(
+            "a xx b",
+            "a{}{}b".lazy_replace_spaced_word("{}", 'x').to_string()
+        );
+    }
+}
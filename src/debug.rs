@@ -0,0 +1,89 @@
+//! `Debug` output for lazily-replaced values.
+
+use std::fmt::{self, Write};
+
+/// A wrapper that implements `Debug` for any type that implements `Display`, rendering it the same
+/// way a `String` holding its output would be: quoted, with special characters escaped.
+///
+/// The output is escaped as it is written, so nothing is allocated.
+pub struct DebugDisplay<'a, D: ?Sized> {
+    inner: &'a D,
+}
+
+impl<'a, D: ?Sized> DebugDisplay<'a, D> {
+    /// Create a new instance of this type
+    pub fn new(inner: &'a D) -> Self {
+        DebugDisplay { inner }
+    }
+}
+
+impl<'a, D> fmt::Debug for DebugDisplay<'a, D>
+where
+    D: fmt::Display + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        write!(DebugEscaper(&mut *f), "{}", self.inner)?;
+        f.write_char('"')
+    }
+}
+
+struct DebugEscaper<W>(W);
+
+impl<W> fmt::Write for DebugEscaper<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            // `str`'s `Debug` impl leaves single quotes alone, unlike `char::escape_debug`
+            if c == '\'' {
+                self.0.write_char(c)?;
+            } else {
+                write!(self.0, "{}", c.escape_debug())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LazyReplace, LazyReplaceDisplay};
+    use std::fmt;
+
+    struct Config<'a> {
+        name: &'a str,
+    }
+
+    impl fmt::Debug for Config<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("Config")
+                .field("name", &self.name.lazy_replace('_', " "))
+                .field(
+                    "quoted",
+                    &"it's \"quoted\"\n"
+                        .replace_display("quoted", "qu'd")
+                        .as_debug(),
+                )
+                .finish()
+        }
+    }
+
+    #[test]
+    fn debug_struct_field() {
+        let config = Config {
+            name: "lazy_string_replace",
+        };
+
+        assert_eq!(
+            format!("{:?}", config),
+            r#"Config { name: "lazy string replace", quoted: "it's \"qu'd\"\n" }"#
+        );
+        assert_eq!(
+            format!("{:?}", "tab_sep".lazy_replace('_', "\t")),
+            format!("{:?}", "tab_sep".replace('_', "\t"))
+        );
+    }
+}
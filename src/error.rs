@@ -0,0 +1,69 @@
+//! Errors returned by the checked constructors and renders.
+
+use std::{error::Error, fmt};
+
+/// The reason a lazily-replaced string couldn't be built by one of the `try_` methods
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplaceBuildError {
+    /// The needle was empty. An empty needle matches before and after every character, so the
+    /// replacement would be inserted between all of them, which is rarely intended.
+    EmptyNeedle,
+    /// Two of the ranges to replace, at the indices `a` and `b`, overlap, so it is unclear which
+    /// of them should be replaced.
+    Overlap {
+        /// The index of the earlier range
+        a: usize,
+        /// The index of the later range
+        b: usize,
+    },
+    /// The range at index `b` starts before the range at index `a` right before it, but the
+    /// ranges to replace must be sorted by their start.
+    UnsortedRanges {
+        /// The index of the earlier range
+        a: usize,
+        /// The index of the later range
+        b: usize,
+    },
+}
+
+impl fmt::Display for ReplaceBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplaceBuildError::EmptyNeedle => {
+                f.write_str("the needle is empty, so it would match between every character")
+            }
+            ReplaceBuildError::Overlap { a, b } => {
+                write!(f, "the ranges at indices {} and {} overlap", a, b)
+            }
+            ReplaceBuildError::UnsortedRanges { a, b } => write!(
+                f,
+                "the range at index {} starts before the one at index {}",
+                b, a
+            ),
+        }
+    }
+}
+
+impl Error for ReplaceBuildError {}
+
+/// The error returned by [`LazyReplace::render_verified`](crate::LazyReplace::render_verified) when
+/// the number of replacements wasn't the expected one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    /// The number of replacements that was expected
+    pub expected: usize,
+    /// The number of replacements that were made
+    pub actual: usize,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected {} replacements, but made {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for VerifyError {}
@@ -0,0 +1,124 @@
+//! Escaping of replacements for the context they are inserted into.
+
+use std::fmt::{self, Write};
+
+/// The contexts [`EscapedFor`] can escape a value for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EscapeProfile {
+    /// HTML text or a quoted attribute value: `&`, `<`, `>`, `"` and `'` are replaced with
+    /// character references
+    Html,
+    /// The inside of a JSON string: `"`, `\` and control characters are escaped with backslashes
+    Json,
+}
+
+/// A wrapper that escapes the output of a `Display` value for an [`EscapeProfile`], so that it can
+/// be used as the replacement of another lazily-replaced string without breaking out of the
+/// surrounding text. Only the wrapped value is escaped, not the text it is inserted into.
+///
+/// The output is escaped as it is written, so nothing is allocated. Formatting options aren't
+/// passed on to the wrapped value.
+pub struct EscapedFor<D> {
+    inner: D,
+    profile: EscapeProfile,
+}
+
+impl<D> EscapedFor<D> {
+    /// Create a new instance of this type
+    pub fn new(inner: D, profile: EscapeProfile) -> Self {
+        EscapedFor { inner, profile }
+    }
+}
+
+/// Wrap a replacement so that its output is escaped for the specified profile before it is emitted.
+/// See [`EscapedFor`].
+pub fn escaped_for<D>(replacement: D, profile: EscapeProfile) -> EscapedFor<D> {
+    EscapedFor::new(replacement, profile)
+}
+
+impl<D> fmt::Display for EscapedFor<D>
+where
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(Escaper(&mut *f, self.profile), "{}", self.inner)
+    }
+}
+
+struct Escaper<W>(W, EscapeProfile);
+
+impl<W> fmt::Write for Escaper<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+
+        while let Some((i, c)) = rest.char_indices().find(|&(_, c)| needs_escape(self.1, c)) {
+            self.0.write_str(&rest[..i])?;
+            match (self.1, c) {
+                (EscapeProfile::Html, '&') => self.0.write_str("&amp;")?,
+                (EscapeProfile::Html, '<') => self.0.write_str("&lt;")?,
+                (EscapeProfile::Html, '>') => self.0.write_str("&gt;")?,
+                (EscapeProfile::Html, '"') => self.0.write_str("&quot;")?,
+                (EscapeProfile::Html, _) => self.0.write_str("&#39;")?,
+                (EscapeProfile::Json, '"') => self.0.write_str("\\\"")?,
+                (EscapeProfile::Json, '\\') => self.0.write_str("\\\\")?,
+                (EscapeProfile::Json, '\n') => self.0.write_str("\\n")?,
+                (EscapeProfile::Json, '\r') => self.0.write_str("\\r")?,
+                (EscapeProfile::Json, '\t') => self.0.write_str("\\t")?,
+                (EscapeProfile::Json, _) => write!(self.0, "\\u{:04x}", c as u32)?,
+            }
+            rest = &rest[i + c.len_utf8()..];
+        }
+
+        self.0.write_str(rest)
+    }
+}
+
+fn needs_escape(profile: EscapeProfile, c: char) -> bool {
+    match profile {
+        EscapeProfile::Html => matches!(c, '&' | '<' | '>' | '"' | '\''),
+        EscapeProfile::Json => matches!(c, '"' | '\\' | '\0'..='\u{1f}'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escaped_for, EscapeProfile};
+    use crate::LazyReplace;
+
+    #[test]
+    fn escape_replacement_for_html() {
+        let user_name = "<script>alert('hi')</script>";
+
+        assert_eq!(
+            "<p>Hello, &lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt;!</p>",
+            "<p>Hello, NAME!</p>"
+                .lazy_replace("NAME", escaped_for(user_name, EscapeProfile::Html))
+                .to_string()
+        );
+
+        // a lazily-replaced replacement is escaped as a whole
+        let inner = "Tom & Jerry".lazy_replace("&", "<&>");
+        assert_eq!(
+            "<b title=\"Tom &lt;&amp;&gt; Jerry\">",
+            "<b title=\"TITLE\">"
+                .lazy_replace("TITLE", escaped_for(inner, EscapeProfile::Html))
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn escape_replacement_for_json() {
+        assert_eq!(
+            r#"{"name": "say \"hi\"\\\n\u0007é"}"#,
+            r#"{"name": "NAME"}"#
+                .lazy_replace(
+                    "NAME",
+                    escaped_for("say \"hi\"\\\n\u{7}é", EscapeProfile::Json)
+                )
+                .to_string()
+        );
+    }
+}
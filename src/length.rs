@@ -0,0 +1,87 @@
+//! Remembering how long a lazily-replaced value turned out to be.
+
+use std::{
+    cell::Cell,
+    fmt::{self, Write},
+};
+
+/// A wrapper that displays a `Display` value as it is and remembers the length of its output in
+/// bytes, so that callers that render a value and then need to know how long it is don't have to
+/// render it a second time just to count.
+///
+/// Only the length is kept, not the output itself. It is `None` until the first render, and each
+/// render that succeeds updates it, so for a value whose output changes between renders it is the
+/// length of the latest one. Formatting options aren't passed on to the wrapped value.
+pub struct LengthCached<D> {
+    inner: D,
+    len: Cell<Option<usize>>,
+}
+
+impl<D> LengthCached<D> {
+    /// Create a new instance of this type
+    pub fn new(inner: D) -> Self {
+        LengthCached {
+            inner,
+            len: Cell::new(None),
+        }
+    }
+
+    /// The length in bytes of the output of the latest render, or `None` if this hasn't been
+    /// rendered yet
+    pub fn cached_len(&self) -> Option<usize> {
+        self.len.get()
+    }
+
+    /// Unwrap this wrapper, returning the wrapped value
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D> fmt::Display for LengthCached<D>
+where
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut counter = Counter { inner: f, len: 0 };
+        write!(counter, "{}", self.inner)?;
+        self.len.set(Some(counter.len));
+        Ok(())
+    }
+}
+
+struct Counter<W> {
+    inner: W,
+    len: usize,
+}
+
+impl<W> fmt::Write for Counter<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        self.inner.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LazyReplace, LazyReplaceDisplay};
+
+    #[test]
+    fn cached_len() {
+        let cached = "a_b_c".lazy_replace('_', "é").with_cached_len();
+        assert_eq!(None, cached.cached_len());
+
+        assert_eq!("aébéc", cached.to_string());
+        assert_eq!(Some(7), cached.cached_len());
+
+        assert_eq!("[aébéc]", format!("[{}]", cached));
+        assert_eq!(Some(7), cached.cached_len());
+
+        let empty = "x".lazy_replace('x', "").with_cached_len();
+        assert_eq!("", empty.to_string());
+        assert_eq!(Some(0), empty.cached_len());
+    }
+}
@@ -8,8 +8,10 @@
 extern crate memchr;
 
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fmt::{self, Write},
-    ops::Deref,
+    ops::{Deref, Range},
 };
 
 #[cfg(not(feature = "nightly"))]
@@ -18,7 +20,84 @@ pub mod pattern;
 #[cfg(feature = "nightly")]
 pub use std::str::pattern;
 
-use self::pattern::{Pattern, SearchStep, Searcher};
+#[cfg(feature = "bidi")]
+extern crate unicode_bidi;
+
+#[cfg(feature = "phf")]
+extern crate phf;
+
+#[cfg(feature = "tokio")]
+extern crate tokio;
+
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "tokio")]
+mod async_write;
+#[cfg(feature = "bidi")]
+mod bidi;
+mod conditional;
+mod debug;
+mod error;
+mod escape;
+mod length;
+mod lines;
+mod matches;
+mod needles;
+#[cfg(feature = "phf")]
+mod phf_map;
+mod ranges;
+mod render;
+mod stream;
+mod transform;
+
+pub use self::{
+    conditional::{
+        ReplacedExact, ReplacedExcept, ReplacedIdentifier, ReplacedInCodeFences, ReplacedLast,
+        ReplacedMinRun, ReplacedOutsideParens, ReplacedSpacedWord, ReplacedSpan, ReplacedToken,
+    },
+    debug::DebugDisplay,
+    error::{ReplaceBuildError, VerifyError},
+    escape::{escaped_for, EscapeProfile, EscapedFor},
+    length::LengthCached,
+    lines::{
+        NumberedLines, ReplacedAtColumns, ReplacedByLineParity, ReplacedWithin,
+        LINE_NUMBER_SEPARATOR,
+    },
+    matches::{
+        ReplaceStrategy, ReplacedAlpha, ReplacedByOrder, ReplacedCow, ReplacedFill, ReplacedFormat,
+        ReplacedIndexedWith, ReplacedMask, ReplacedOpt, ReplacedOrdinal, ReplacedPlural,
+        ReplacedScan, ReplacedStrategy, ReplacedTimestamp, ReplacedWith,
+    },
+    needles::{
+        Dehyphenated, DehyphenatedSearcher, Highlighted, NeedleSetSearcher, ReplacedPairs,
+        ReplacedSorted, ReplacedTable, StaticNeedleSet, StaticNeedles, StaticNeedlesSearcher,
+        Swapped, ANSI_RESET,
+    },
+    ranges::ReplacedRanges,
+    render::{
+        detokenize, replace_to_fixed_point, ReplacePreview, ANNOTATION_END, ANNOTATION_START,
+        TOKEN_DELIMITER,
+    },
+    transform::{
+        Encoding, ReplacedEncode, ReplacedGroupDigits, ReplacedNato, ReplacedPercentDecode,
+    },
+};
+
+// used by `needle_set!`
+#[doc(hidden)]
+pub use self::needles::{build_trie, trie_len, TrieNode};
+
+#[cfg(feature = "tokio")]
+pub use self::async_write::ReplaceAsyncWriter;
+
+#[cfg(feature = "phf")]
+pub use self::phf_map::ReplacedPhf;
+
+use self::{
+    pattern::{Pattern, SearchStep, Searcher},
+    stream::{Piece, StreamMatcher},
+};
 
 /// A type to lazily replace strings in any type that implements `Display`
 pub struct ReplaceDisplay<'a, H, R> {
@@ -44,22 +123,29 @@ where
     R: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            ReplaceWriter::new(f, self.needle, &self.replacement),
-            "{}",
-            self.haystack
-        )
+        let precision = f.precision();
+        let mut writer =
+            ReplaceWriter::new(f, self.needle, &self.replacement).with_precision(precision);
+        write!(writer, "{}", self.haystack)?;
+        writer.finish()
     }
 }
 
 /// A wrapper around a `fmt::Write` that does string replacement on anything that is written to it
 /// before passing it to the underlying writer.
+///
+/// Matches may be split across any number of writes, like the pieces of a `format_args!`. The end
+/// of what was written so far is held back for as long as it could still be the start of a match,
+/// so [`finish`](ReplaceWriter::finish) must be called once everything has been written, to write
+/// out whatever is left.
+///
+/// Like `str::replace`, an empty needle matches before and after every character.
 pub struct ReplaceWriter<'a, W, R> {
-    writer: W,
-    needle_pos: usize,
+    out: BufferedWriter<W>,
+    matcher: StreamMatcher,
     needle: &'a str,
     replacement: R,
-    buffer: String,
+    precision: Option<usize>,
 }
 
 impl<'a, W, R> ReplaceWriter<'a, W, R>
@@ -68,14 +154,92 @@ where
     R: fmt::Display,
 {
     /// Create a new instance of this type
+    ///
+    /// [`finish`](ReplaceWriter::finish) must be called once everything has been written. The
+    /// writer doesn't write anything out when it is dropped, so without that, the end that was held
+    /// back as the possible start of a match is lost.
     pub fn new(writer: W, needle: &'a str, replacement: R) -> Self {
+        Self::with_flush_threshold(writer, needle, replacement, 0)
+    }
+
+    /// Create a new instance of this type that collects its output in a buffer, and only passes it
+    /// on to `writer` once at least `threshold` bytes have been collected, or when it is finished.
+    ///
+    /// This turns many small writes, of the text between matches and of each replacement, into
+    /// fewer large ones, which helps with writers that are slow to call. In exchange, up to about
+    /// `threshold` bytes are kept in memory, and the output reaches `writer` later. A threshold of 0
+    /// passes everything on right away, like [`new`](ReplaceWriter::new).
+    ///
+    /// As with `new`, [`finish`](ReplaceWriter::finish) must be called once everything has been
+    /// written. Dropping the writer without that loses everything that is still in the buffer,
+    /// which, below the threshold, is all of the output.
+    pub fn with_flush_threshold(
+        writer: W,
+        needle: &'a str,
+        replacement: R,
+        threshold: usize,
+    ) -> Self {
         ReplaceWriter {
-            writer,
-            needle_pos: 0,
+            out: BufferedWriter {
+                writer,
+                buffer: String::new(),
+                threshold,
+            },
+            matcher: StreamMatcher::default(),
+            needle,
+            replacement,
+            precision: None,
+        }
+    }
+
+    /// Write each replacement with the specified precision, as [`ReplaceDisplay`] does with the
+    /// precision of its formatter
+    pub(crate) fn with_precision(mut self, precision: Option<usize>) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Write out anything that was held back as the possible start of a match, and, for an empty
+    /// needle, the replacement for the match at the very end. Afterwards, this writer can be
+    /// written to again as if it was new.
+    pub fn finish(&mut self) -> fmt::Result {
+        let ReplaceWriter {
+            out,
+            matcher,
             needle,
             replacement,
-            buffer: String::new(),
+            precision,
+        } = self;
+
+        if needle.is_empty() {
+            write_with_precision(out, *precision, replacement)?;
+        } else {
+            matcher.finish(needle.as_bytes(), |piece| {
+                write_piece(out, replacement, *precision, piece)
+            })?;
         }
+
+        out.flush()
+    }
+}
+
+/// Write a piece produced by a [`StreamMatcher`], which is valid UTF-8 when the chunks fed into it
+/// were `str`s
+fn write_piece<W, R>(
+    writer: &mut W,
+    replacement: &R,
+    precision: Option<usize>,
+    piece: Piece<'_>,
+) -> fmt::Result
+where
+    W: fmt::Write,
+    R: fmt::Display,
+{
+    match piece {
+        Piece::Literal(bytes) => writer.write_str(
+            std::str::from_utf8(bytes).expect("pieces are only split at character boundaries"),
+        ),
+        Piece::Match => write_with_precision(writer, precision, replacement),
     }
 }
 
@@ -85,57 +249,63 @@ where
     R: fmt::Display,
 {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        let rest_needle = &self.needle[self.needle_pos..];
+        let ReplaceWriter {
+            out,
+            matcher,
+            needle,
+            replacement,
+            precision,
+        } = self;
 
-        if s.len() < rest_needle.len() && s.starts_with(&rest_needle[..s.len()]) {
-            self.needle_pos += s.len();
-            self.buffer.push_str(s);
-        } else {
-            self.needle_pos = 0;
-
-            if s == rest_needle {
-                self.buffer.clear();
-                write!(self.writer, "{}", self.replacement)?;
-            } else {
-                self.writer.write_str(&self.buffer)?;
-                self.buffer.clear();
-
-                if let Some(first_char) = self.needle.chars().next() {
-                    let mut s = s;
-
-                    while let Some(i) = s.find(first_char) {
-                        self.writer.write_str(&s[..i])?;
-                        s = &s[i..];
-
-                        let mut len = first_char.len_utf8();
-                        let needle_bytes = self.needle.as_bytes();
-                        let s_bytes = s.as_bytes();
-
-                        while needle_bytes
-                            .get(len)
-                            .and_then(|needle| s_bytes.get(len).map(|haystack| haystack == needle))
-                            .unwrap_or(false)
-                        {
-                            len += 1;
-                        }
-
-                        if len == self.needle.len() {
-                            write!(self.writer, "{}", self.replacement)?;
-                            s = &s[len..];
-                        } else if len == s.len() {
-                            self.buffer.push_str(&s[i..]);
-                            self.needle_pos = len;
-
-                            return Ok(());
-                        } else {
-                            self.writer.write_str(&s[..len])?;
-                            s = &s[len..];
-                        }
-                    }
-
-                    self.writer.write_str(s)?;
-                }
+        if needle.is_empty() {
+            for c in s.chars() {
+                write_with_precision(out, *precision, replacement)?;
+                out.write_char(c)?;
             }
+
+            return Ok(());
+        }
+
+        matcher.feed(needle.as_bytes(), s.as_bytes(), |piece| {
+            write_piece(out, replacement, *precision, piece)
+        })
+    }
+}
+
+/// The output of a [`ReplaceWriter`], which collects everything written to it until there are at
+/// least `threshold` bytes before passing them on
+struct BufferedWriter<W> {
+    writer: W,
+    buffer: String,
+    threshold: usize,
+}
+
+impl<W> BufferedWriter<W>
+where
+    W: fmt::Write,
+{
+    fn flush(&mut self) -> fmt::Result {
+        if !self.buffer.is_empty() {
+            self.writer.write_str(&self.buffer)?;
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+}
+
+impl<W> fmt::Write for BufferedWriter<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.threshold == 0 {
+            return self.writer.write_str(s);
+        }
+
+        self.buffer.push_str(s);
+        if self.buffer.len() >= self.threshold {
+            self.flush()?;
         }
 
         Ok(())
@@ -143,6 +313,10 @@ where
 }
 
 /// A lazily-replaced string - no work is done until you call `.to_string()` or use `format!`/`write!` and friends. This is useful when, for example, doing `format!("( {} )", my_string.replace(needle, some_replacement)`. Since it uses a `Display` for a replacement, you can even replace a string with a different lazily-replaced string, all without allocating. Of course, this will duplicate work when there is more than one match, but fixing this would require memoization of the `Display` result, which in turn would require allocation. A memoizing `Display` wrapper is out of scope for this crate.
+///
+/// An empty needle matches at every character boundary, including the start and the end of the haystack, so the replacement is inserted between all characters and around them exactly like `str::replace` does it: `"ab".lazy_replace("", '-')` gives `"-a-b-"`, and an empty haystack gives just the replacement.
+///
+/// The precision of the formatter is passed on to each replacement, so `format!("{:.2}", "pi is PI".lazy_replace("PI", 3.14159))` gives `"pi is 3.14"`, and a `str` replacement is cut off after that many characters. The rest of the haystack is written without it. Width, alignment and the other flags are ignored: they aren't applied to each replacement, and not to the output as a whole either. The same goes for the other lazily-replaced types in this crate, except for those documented to use the width themselves, like [`ReplacedFill`].
 pub struct ReplacedString<'a, P, R> {
     haystack: &'a str,
     needle: P,
@@ -164,6 +338,444 @@ impl<'a, P, R> ReplacedString<'a, P, R> {
 pub trait LazyReplace {
     /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement
     fn lazy_replace<P, R>(&self, pat: P, replacement: R) -> ReplacedString<'_, P, R>;
+
+    /// The string that `lazy_replace` searches in, for the other methods to build on
+    #[doc(hidden)]
+    fn as_haystack(&self) -> &str;
+
+    /// Like `lazy_replace`, but returns an error instead if the needle is empty
+    fn try_lazy_replace<'n, R>(
+        &self,
+        needle: &'n str,
+        replacement: R,
+    ) -> Result<ReplacedString<'_, &'n str, R>, ReplaceBuildError> {
+        if needle.is_empty() {
+            Err(ReplaceBuildError::EmptyNeedle)
+        } else {
+            Ok(self.lazy_replace(needle, replacement))
+        }
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each of the specified byte ranges replaced with the specified replacement, instead of searching for a pattern. The ranges should be sorted and not overlap; see [`ReplacedRanges`] for what happens otherwise.
+    fn lazy_replace_at_ranges<'r, R>(
+        &self,
+        ranges: &'r [Range<usize>],
+        replacement: R,
+    ) -> ReplacedRanges<'_, 'r, R> {
+        ReplacedRanges::new(self.as_haystack(), ranges, replacement)
+    }
+
+    /// Like `lazy_replace_at_ranges`, but returns an error instead if any two of the ranges overlap or they aren't sorted by their start
+    fn try_lazy_replace_at_ranges<'r, R>(
+        &self,
+        ranges: &'r [Range<usize>],
+        replacement: R,
+    ) -> Result<ReplacedRanges<'_, 'r, R>, ReplaceBuildError> {
+        ReplacedRanges::try_new(self.as_haystack(), ranges, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with every needle in the specified set replaced with the specified replacement
+    fn lazy_replace_set<'s, R>(
+        &self,
+        set: &'s StaticNeedleSet,
+        replacement: R,
+    ) -> ReplacedString<'_, &'s StaticNeedleSet, R> {
+        self.lazy_replace(set, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the matches of each `(needle, style)` pair wrapped in that ANSI style and a reset, so that different needles can be highlighted in different colors
+    fn lazy_highlight_many<'p>(&self, pairs: &'p [(&'p str, &'p str)]) -> Highlighted<'_, 'p> {
+        Highlighted::new(self.as_haystack(), pairs)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the needle of each `(needle, replacement)` pair from the specified iterator replaced with its replacement, preferring the longest needle where several match at the same position. The pairs are collected up front.
+    fn lazy_replace_iter_needles<'n, I, R>(&self, pairs: I) -> ReplacedPairs<'_, 'n, R>
+    where
+        I: IntoIterator<Item = (&'n str, R)>,
+    {
+        ReplacedPairs::new(self.as_haystack(), pairs)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with every one of the specified needles replaced with the specified replacement. Unlike [`lazy_replace_set`](LazyReplace::lazy_replace_set), this needs no set to be built up front; see [`StaticNeedles`] for how larger slices are cached instead.
+    fn lazy_replace_static<R>(
+        &self,
+        needles: &'static [&'static str],
+        replacement: R,
+    ) -> ReplacedString<'_, StaticNeedles, R> {
+        self.lazy_replace(StaticNeedles::new(needles), replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified needle replaced with the specified replacement, even where the needle is split across lines by a hyphen. See [`Dehyphenated`] for how these soft breaks are handled.
+    fn lazy_replace_dehyphenated<'n, R>(
+        &self,
+        needle: &'n str,
+        replacement: R,
+    ) -> ReplacedString<'_, Dehyphenated<'n>, R> {
+        self.lazy_replace(Dehyphenated(needle), replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with every occurrence of `a` replaced with `b` and every occurrence of `b` with `a`, in a single pass
+    fn lazy_swap<'n>(&self, a: &'n str, b: &'n str) -> Swapped<'_, 'n> {
+        Swapped::new(self.as_haystack(), a, b)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each ASCII character replaced with the entry for its code in the specified table, if that entry isn't `None`
+    fn lazy_replace_table<'t, R>(&self, table: &'t [Option<R>; 128]) -> ReplacedTable<'_, 't, R> {
+        ReplacedTable::new(self.as_haystack(), table)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each key of the specified table replaced with its value. The table must be sorted by key, and keys are looked up with a binary search; see [`ReplacedSorted`] for how keys of different lengths are matched.
+    fn lazy_replace_sorted<'t>(&self, table: &'t [(&'t str, &'t str)]) -> ReplacedSorted<'_, 't> {
+        ReplacedSorted::new(self.as_haystack(), table)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each key of the specified compile-time `phf::Map` replaced with its value. See [`ReplacedPhf`] for how keys of different lengths are matched. Requires the `phf` feature.
+    #[cfg(feature = "phf")]
+    fn lazy_replace_phf<'m>(
+        &self,
+        map: &'m phf::Map<&'static str, &'static str>,
+    ) -> ReplacedPhf<'_, 'm> {
+        ReplacedPhf::new(self.as_haystack(), map)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced by the result of calling `f` with an accumulator (starting at `init` on every render) and the matched text
+    fn lazy_replace_scan<P, S, F, D>(&self, pat: P, init: S, f: F) -> ReplacedScan<'_, P, S, F>
+    where
+        F: FnMut(&mut S, &str) -> D,
+    {
+        ReplacedScan::new(self.as_haystack(), pat, init, f)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the `Cow` returned by calling `f` with the matched text. Matches that `f` returns borrowed are written without allocating.
+    fn lazy_replace_cow<P, F>(&self, pat: P, f: F) -> ReplacedCow<'_, P, F>
+    where
+        F: Fn(&str) -> Cow<'_, str>,
+    {
+        ReplacedCow::new(self.as_haystack(), pat, f)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the `String` returned by calling `make` with the matched text
+    fn lazy_replace_with_string<P, F>(&self, pat: P, make: F) -> ReplacedWith<'_, P, F>
+    where
+        F: Fn(&str) -> String,
+    {
+        ReplacedWith::new(self.as_haystack(), pat, make)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the plural form that `rule` picks for `count`, like `"item"` or `"items"`. `rule` is called once whenever the result is rendered.
+    fn lazy_replace_plural<P, F, D>(&self, pat: P, count: i64, rule: F) -> ReplacedPlural<'_, P, F>
+    where
+        F: Fn(i64) -> D,
+        D: fmt::Display,
+    {
+        ReplacedPlural::new(self.as_haystack(), pat, count, rule)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the result of calling `f`, such as a timestamp. `f` is called for each match whenever the result is rendered, never up front.
+    fn lazy_replace_timestamp<P, F, D>(&self, pat: P, f: F) -> ReplacedTimestamp<'_, P, F>
+    where
+        F: Fn() -> D,
+        D: fmt::Display,
+    {
+        ReplacedTimestamp::new(self.as_haystack(), pat, f)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement if there is one, or left as it is if the replacement is `None`
+    fn lazy_replace_opt<P, R>(&self, pat: P, replacement: Option<R>) -> ReplacedOpt<'_, P, R> {
+        ReplacedOpt::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the result of calling `f` with the 0-based index of the match and the matched text
+    fn lazy_replace_indexed_with<P, F, D>(&self, pat: P, f: F) -> ReplacedIndexedWith<'_, P, F>
+    where
+        F: FnMut(usize, &str) -> D,
+        D: fmt::Display,
+    {
+        ReplacedIndexedWith::new(self.as_haystack(), pat, f)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each character of each match of the specified pattern replaced with `mask`, so the masked text is exactly as many characters long as the original
+    fn lazy_replace_mask<P>(&self, pat: P, mask: char) -> ReplacedMask<'_, P> {
+        ReplacedMask::new(self.as_haystack(), pat, mask)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced according to the specified strategy, which can be chosen at runtime
+    fn lazy_replace_strategy<P>(
+        &self,
+        pat: P,
+        strategy: ReplaceStrategy,
+    ) -> ReplacedStrategy<'_, P> {
+        ReplacedStrategy::new(self.as_haystack(), pat, strategy)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with `fill` repeated to the width requested from the formatter, or to the length of the match if there is none
+    fn lazy_replace_fill<P>(&self, pat: P, fill: char) -> ReplacedFill<'_, P> {
+        ReplacedFill::new(self.as_haystack(), pat, fill)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with its 1-based ordinal, like `first` or `11th`
+    fn lazy_replace_ordinal<P>(&self, pat: P) -> ReplacedOrdinal<'_, P> {
+        ReplacedOrdinal::new(self.as_haystack(), pat)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the i-th match of the specified pattern replaced with `values[i]`. Matches past the end of `values` are left as they are.
+    fn lazy_replace_by_order<'v, P, R>(
+        &self,
+        pat: P,
+        values: &'v [R],
+    ) -> ReplacedByOrder<'_, 'v, P, R> {
+        ReplacedByOrder::new(self.as_haystack(), pat, values)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the matches of the specified pattern replaced with the labels `a` to `z`, followed by `aa`, `ab` and so on
+    fn lazy_replace_alpha<P>(&self, pat: P) -> ReplacedAlpha<'_, P> {
+        ReplacedAlpha::new(self.as_haystack(), pat)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the specified template, where `{}` stands for the matched text and `{{` and `}}` for literal braces
+    fn lazy_replace_format<'t, P>(&self, pat: P, template: &'t str) -> ReplacedFormat<'_, 't, P> {
+        ReplacedFormat::new(self.as_haystack(), pat, template)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each run of digits matched by the specified pattern grouped in threes with `sep`, like `1,234,567`. See [`ReplacedGroupDigits`] for how matches form runs.
+    fn lazy_replace_group_digits<P>(&self, pat: P, sep: char) -> ReplacedGroupDigits<'_, P> {
+        ReplacedGroupDigits::new(self.as_haystack(), pat, sep)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern spelled out in the NATO phonetic alphabet, like `Sierra Oscar Sierra`. Characters other than ASCII letters are kept as they are.
+    fn lazy_replace_nato<P>(&self, pat: P) -> ReplacedNato<'_, P> {
+        ReplacedNato::new(self.as_haystack(), pat)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern replaced with the specified encoding of its UTF-8 bytes, like hex or base64
+    fn lazy_replace_encode<P>(&self, pat: P, encoding: Encoding) -> ReplacedEncode<'_, P> {
+        ReplacedEncode::new(self.as_haystack(), pat, encoding)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each match of the specified pattern percent-decoded, leaving malformed escapes as they are
+    fn lazy_replace_percent_decode<P>(&self, pat: P) -> ReplacedPercentDecode<'_, P> {
+        ReplacedPercentDecode::new(self.as_haystack(), pat)
+    }
+
+    /// Create a struct implementing `Display` that will display the specified replacement if this string is exactly equal to the specified needle, and this string as it is otherwise
+    fn lazy_replace_exact<'n, R>(
+        &self,
+        needle: &'n str,
+        replacement: R,
+    ) -> ReplacedExact<'_, 'n, R> {
+        ReplacedExact::new(self.as_haystack(), needle, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only where the match is surrounded by the specified delimiters or the ends of this string
+    fn lazy_replace_token<'d, P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+        delims: &'d [char],
+    ) -> ReplacedToken<'_, 'd, P, R> {
+        ReplacedToken::new(self.as_haystack(), pat, replacement, delims)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, adding a space on either side where the match is next to a word character. See [`ReplacedSpacedWord`] for the exact rules.
+    fn lazy_replace_spaced_word<P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+    ) -> ReplacedSpacedWord<'_, P, R> {
+        ReplacedSpacedWord::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only where the match is a whole identifier. See [`ReplacedIdentifier`] for what counts as one.
+    fn lazy_replace_identifiers<P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+    ) -> ReplacedIdentifier<'_, P, R> {
+        ReplacedIdentifier::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with each run of characters matching the specified predicate replaced with the specified replacement, as long as the run is at least `min_len` characters long
+    fn lazy_replace_min_run<F, R>(
+        &self,
+        pat: F,
+        replacement: R,
+        min_len: usize,
+    ) -> ReplacedMinRun<'_, F, R>
+    where
+        F: Fn(char) -> bool,
+    {
+        ReplacedMinRun::new(self.as_haystack(), pat, replacement, min_len)
+    }
+
+    /// Create a struct implementing `Display` that will display this Markdown string with the specified pattern replaced with the specified replacement, but only inside fenced code blocks. See [`ReplacedInCodeFences`] for what counts as one.
+    fn lazy_replace_in_code_fences<P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+    ) -> ReplacedInCodeFences<'_, P, R> {
+        ReplacedInCodeFences::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement everywhere except inside the matches of the `skip` pattern. See [`ReplacedExcept`] for how overlaps between the two are handled.
+    fn lazy_replace_except<P, S, R>(
+        &self,
+        pat: P,
+        replacement: R,
+        skip: S,
+    ) -> ReplacedExcept<'_, P, S, R> {
+        ReplacedExcept::new(self.as_haystack(), pat, skip, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with only the last match of the specified pattern replaced with the specified replacement, searching from the end like [`str::rfind`]
+    fn lazy_replace_last<P, R>(&self, pat: P, replacement: R) -> ReplacedLast<'_, P, R> {
+        ReplacedLast::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the span from the first match of `start_pat` up to the last match of `end_pat` after it replaced with the specified replacement as a whole. See [`ReplacedSpan`] for when the string is left unchanged instead.
+    fn lazy_replace_span<S, E, R>(
+        &self,
+        start_pat: S,
+        end_pat: E,
+        replacement: R,
+    ) -> ReplacedSpan<'_, S, E, R> {
+        ReplacedSpan::new(self.as_haystack(), start_pat, end_pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only where the match isn't inside parentheses
+    fn lazy_replace_outside_parens<P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+    ) -> ReplacedOutsideParens<'_, P, R> {
+        ReplacedOutsideParens::new(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only on its first line
+    fn lazy_replace_first_line<P, R>(&self, pat: P, replacement: R) -> ReplacedWithin<'_, P, R> {
+        ReplacedWithin::first_line(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only on its last line
+    fn lazy_replace_last_line<P, R>(&self, pat: P, replacement: R) -> ReplacedWithin<'_, P, R> {
+        ReplacedWithin::last_line(self.as_haystack(), pat, replacement)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the matches of the specified pattern replaced with `even` on even-numbered lines and `odd` on odd-numbered ones, counting lines from 1
+    fn lazy_replace_by_line_parity<P, E, O>(
+        &self,
+        pat: P,
+        even: E,
+        odd: O,
+    ) -> ReplacedByLineParity<'_, P, E, O> {
+        ReplacedByLineParity::new(self.as_haystack(), pat, even, odd)
+    }
+
+    /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement, but only where the match starts at one of the specified columns (counted in characters from 0) of its line
+    fn lazy_replace_at_columns<'c, P, R>(
+        &self,
+        pat: P,
+        replacement: R,
+        columns: &'c [usize],
+    ) -> ReplacedAtColumns<'_, 'c, P, R> {
+        ReplacedAtColumns::new(self.as_haystack(), pat, replacement, columns)
+    }
+
+    /// Render this string with the specified pattern replaced with the specified replacement, marking each change like `git diff --word-diff` does: the matched text is wrapped in `[-` and `-]`, directly followed by the replacement wrapped in `{+` and `+}`. Everything else is left as it is.
+    fn render_diff<'a, P, R>(&'a self, pat: P, replacement: R) -> String
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::diff(self.as_haystack(), pat, replacement)
+    }
+
+    /// Render this string with the specified pattern replaced with the specified replacement, putting [`ANNOTATION_START`] in front of each replacement and [`ANNOTATION_END`] behind it, so that tools further down the line can tell where the replacements are. The markers are the Unicode bidirectional isolates, which are invisible and, in left-to-right text, don't change how the text is displayed, and removing them gives the same output as `lazy_replace`.
+    fn render_annotated<'a, P, R>(&'a self, pat: P, replacement: R) -> String
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::annotated(self.as_haystack(), pat, replacement)
+    }
+
+    /// Find each match of the specified pattern along with its byte range and the rendered replacement, as a review UI would show them. The matches are searched for in one pass, and the replacement is rendered once per match.
+    fn preview<'a, P, R>(&'a self, pat: P, replacement: R) -> Vec<ReplacePreview<'a>>
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::preview(self.as_haystack(), pat, replacement)
+    }
+
+    /// Render this string with the specified pattern replaced with the specified replacement, checking that exactly `expected_count` replacements were made. If a different number was made, the output is discarded and a [`VerifyError`] with both numbers is returned instead.
+    fn render_verified<'a, P, R>(
+        &'a self,
+        pat: P,
+        replacement: R,
+        expected_count: usize,
+    ) -> Result<String, VerifyError>
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::verified(self.as_haystack(), pat, replacement, expected_count)
+    }
+
+    /// Render this string with each match of the specified pattern replaced with what `f` returns for it, if that succeeds. Matches that `f` fails for are kept as they are, and the errors are returned along with the byte ranges of the matches they were returned for, in order.
+    fn render_try_with<'a, P, F, D, E>(&'a self, pat: P, f: F) -> (String, Vec<(Range<usize>, E)>)
+    where
+        P: Pattern<'a>,
+        F: FnMut(&str) -> Result<D, E>,
+        D: fmt::Display,
+    {
+        render::try_with(self.as_haystack(), pat, f)
+    }
+
+    /// Render this string with each match of the specified pattern replaced with a numbered placeholder token, [`TOKEN_DELIMITER`] followed by the 0-based index of the match and another [`TOKEN_DELIMITER`], returning it along with the matched texts in order. [`detokenize`] turns the tokens back into the matched texts, even after the text around them was edited.
+    fn render_tokenized<'a, P>(&'a self, pat: P) -> (String, Vec<String>)
+    where
+        P: Pattern<'a>,
+    {
+        render::tokenized(self.as_haystack(), pat)
+    }
+
+    /// Render a snippet for each match of the specified pattern, like `grep` does, containing the specified replacement with up to `before` characters of this string in front of it and up to `after` characters behind it. The surrounding characters are taken from this string as they are, even if they contain other matches.
+    fn render_context<'a, P, R>(
+        &'a self,
+        pat: P,
+        replacement: R,
+        before: usize,
+        after: usize,
+    ) -> Vec<String>
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::context(self.as_haystack(), pat, replacement, before, after)
+    }
+
+    /// Render this string twice, once with the matches of the specified pattern replaced with `a` and once with them replaced with `b`. The matches are only searched for once.
+    fn render_both<'a, P, A, B>(&'a self, pat: P, a: A, b: B) -> (String, String)
+    where
+        P: Pattern<'a>,
+        A: fmt::Display,
+        B: fmt::Display,
+    {
+        render::both(self.as_haystack(), pat, a, b)
+    }
+
+    /// Render this string with the specified pattern replaced with the specified replacement, also counting how many times each distinct matched text occurred. This is mostly useful with patterns that can match different text, like a `char` predicate.
+    fn render_with_frequency<'a, P, R>(
+        &'a self,
+        pat: P,
+        replacement: R,
+    ) -> (String, HashMap<String, usize>)
+    where
+        P: Pattern<'a>,
+        R: fmt::Display,
+    {
+        render::with_frequency(self.as_haystack(), pat, replacement)
+    }
 }
 
 impl<T> LazyReplace for T
@@ -174,9 +786,13 @@ where
         ReplacedString {
             needle,
             replacement,
-            haystack: &*self,
+            haystack: self,
         }
     }
+
+    fn as_haystack(&self) -> &str {
+        self
+    }
 }
 
 impl LazyReplace for str {
@@ -184,9 +800,13 @@ impl LazyReplace for str {
         ReplacedString {
             needle,
             replacement,
-            haystack: &*self,
+            haystack: self,
         }
     }
+
+    fn as_haystack(&self) -> &str {
+        self
+    }
 }
 
 /// A convenience trait to allow you to call `.replace_display` on anything that implements `fmt::Display`.
@@ -194,6 +814,91 @@ impl LazyReplace for str {
 pub trait LazyReplaceDisplay: Sized {
     /// Create a struct implementing `Display` that will display this string with the specified pattern replaced with the specified replacement
     fn replace_display<'a, R>(self, pat: &'a str, replacement: R) -> ReplaceDisplay<'a, Self, R>;
+
+    /// Create a struct implementing `Display` that will display this value with every line prefixed by its line number, starting at `start`. The numbers are aligned to the width of the largest one, so this value is rendered twice; see [`NumberedLines`].
+    fn lazy_number_lines(&self, start: usize) -> NumberedLines<&Self> {
+        NumberedLines::new(self, start)
+    }
+
+    /// Create a struct implementing `Display` that will display this value with every line prefixed by its line number, starting at `start` and right-aligned to `width` columns, streaming it through line by line
+    fn lazy_number_lines_with_width(&self, start: usize, width: usize) -> NumberedLines<&Self> {
+        NumberedLines::with_width(self, start, width)
+    }
+
+    /// Wrap this value so that the length of its output in bytes is remembered whenever it is rendered. See [`LengthCached`].
+    fn with_cached_len(self) -> LengthCached<Self> {
+        LengthCached::new(self)
+    }
+
+    /// Borrow this value as something implementing `Debug`, which renders it like a `String` containing its output would be
+    fn as_debug(&self) -> DebugDisplay<'_, Self>
+    where
+        Self: fmt::Display,
+    {
+        DebugDisplay::new(self)
+    }
+
+    /// Borrow this value as a `tracing` field value, like `tracing::info!(text = %value)` does. Nothing is rendered when the event is created: the value is only formatted once a subscriber records the event, so nothing is formatted at all if its level is disabled. Requires the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    fn as_field(&self) -> tracing::field::DisplayValue<&Self>
+    where
+        Self: fmt::Display,
+    {
+        tracing::field::display(self)
+    }
+
+    /// Render this value as a single line, with each line break between two lines replaced by `join`. `\r\n` counts as one line break, and line breaks at the very start or end are dropped.
+    fn render_flattened(&self, join: &str) -> String
+    where
+        Self: fmt::Display,
+    {
+        lines::flattened(self, join)
+    }
+
+    /// Render this value into any collection of characters, like `Vec<char>` or a custom string type, without going through a `String`. The characters are added one by one, as they are written.
+    fn collect_into<S>(&self) -> S
+    where
+        Self: fmt::Display,
+        S: Extend<char> + Default,
+    {
+        render::collect_into(self)
+    }
+
+    /// Feed what this value renders to into the specified hasher, without collecting it into a `String`, and return the hasher's `finish` value. The rendered UTF-8 bytes are passed to `Hasher::write` in the pieces they are written in, so whether the result equals that of hashing the rendered string with a single `Hasher::write` call depends on the hasher. `Hasher` doesn't require that, and `DefaultHasher` doesn't promise it. It isn't the same as [`Hash`](std::hash::Hash)ing a `str`, which adds a terminator.
+    fn digest<H>(&self, hasher: &mut H) -> u64
+    where
+        Self: fmt::Display,
+        H: std::hash::Hasher,
+    {
+        render::digest(self, hasher)
+    }
+
+    /// Compare what this value renders to with `expected`, returning the byte offset of the first character where they differ along with that character from each of them, or `None` if they are equal. Where one of them ends before the other, its character is `None`. Rendering stops at the first difference.
+    fn diff_against(&self, expected: &str) -> Option<(usize, Option<char>, Option<char>)>
+    where
+        Self: fmt::Display,
+    {
+        render::diff_against(self, expected)
+    }
+
+    /// Render this value as a table, with rows separated by `row_sep` and cells within them separated by `col_sep`, padding each cell with spaces so the columns line up across rows. Widths are counted in characters, and the last cell of each row isn't padded.
+    fn render_aligned(&self, row_sep: char, col_sep: char) -> String
+    where
+        Self: fmt::Display,
+    {
+        render::aligned(self, row_sep, col_sep)
+    }
+
+    /// Render this value and reorder it for display according to the Unicode Bidirectional Algorithm, so that right-to-left runs like Hebrew or Arabic text appear in visual order.
+    ///
+    /// Each line is reordered on its own, as a single paragraph whose direction is taken from its first strongly directional character. Lines aren't wrapped, and characters like brackets aren't mirrored. Requires the `bidi` feature.
+    #[cfg(feature = "bidi")]
+    fn render_bidi(&self) -> String
+    where
+        Self: fmt::Display,
+    {
+        bidi::render(self)
+    }
 }
 
 impl<T> LazyReplaceDisplay for T
@@ -215,8 +920,8 @@ where
         let mut searcher = self.needle.clone().into_searcher(self.haystack);
         loop {
             match searcher.next() {
-                SearchStep::Match(_, _) => write!(f, "{}", self.replacement)?,
-                SearchStep::Reject(start, end) => write!(f, "{}", &self.haystack[start..end])?,
+                SearchStep::Match(_, _) => write_replacement(f, &self.replacement)?,
+                SearchStep::Reject(start, end) => f.write_str(&self.haystack[start..end])?,
                 SearchStep::Done => break,
             }
         }
@@ -225,9 +930,74 @@ where
     }
 }
 
+impl<'a, P, R> fmt::Debug for ReplacedString<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_debug(), f)
+    }
+}
+
+/// Write `replacement` to `f` with the precision of `f`, if it has one, but none of its other
+/// options, which are meant for the output as a whole rather than each replacement
+pub(crate) fn write_replacement<R>(f: &mut fmt::Formatter, replacement: &R) -> fmt::Result
+where
+    R: fmt::Display + ?Sized,
+{
+    let precision = f.precision();
+    write_with_precision(f, precision, replacement)
+}
+
+/// Write `replacement` to `w` with the specified precision, if any. See [`write_replacement`].
+pub(crate) fn write_with_precision<W, R>(
+    w: &mut W,
+    precision: Option<usize>,
+    replacement: &R,
+) -> fmt::Result
+where
+    W: Write + ?Sized,
+    R: fmt::Display + ?Sized,
+{
+    match precision {
+        Some(precision) => write!(w, "{:.*}", precision, replacement),
+        None => write!(w, "{}", replacement),
+    }
+}
+
+/// Search `haystack` for `pat`, writing everything that doesn't match to `w` verbatim and passing
+/// the index and range of each match to `on_match`
+pub(crate) fn write_matches<'a, W, P, F>(
+    w: &mut W,
+    haystack: &'a str,
+    pat: P,
+    mut on_match: F,
+) -> fmt::Result
+where
+    W: Write + ?Sized,
+    P: Pattern<'a>,
+    F: FnMut(&mut W, usize, Range<usize>) -> fmt::Result,
+{
+    let mut searcher = pat.into_searcher(haystack);
+    let mut index = 0;
+
+    loop {
+        match searcher.next() {
+            SearchStep::Match(start, end) => {
+                on_match(w, index, start..end)?;
+                index += 1;
+            }
+            SearchStep::Reject(start, end) => w.write_str(&haystack[start..end])?,
+            SearchStep::Done => return Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{LazyReplace, LazyReplaceDisplay};
+    use super::{LazyReplace, LazyReplaceDisplay, ReplaceBuildError, ReplaceWriter};
+    use std::fmt::{self, Write};
 
     #[test]
     fn replace_string() {
@@ -247,6 +1017,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn replacement_gets_the_precision() {
+        assert_eq!(
+            "pi is 3.14, or about 3.14",
+            format!(
+                "{:.2}",
+                "pi is PI, or about PI".lazy_replace("PI", std::f64::consts::PI)
+            )
+        );
+        assert_eq!("[1.5]", format!("{:.1}", "[X]".lazy_replace('X', 1.4999)));
+        assert_eq!(
+            "hello wo",
+            format!("{:.2}", "hello NAME".lazy_replace("NAME", "world"))
+        );
+        assert_eq!(
+            "pi is π",
+            format!("{:.2}", "pi is PI".lazy_replace("PI", 'π'))
+        );
+
+        // the same goes for the other wrappers
+        assert_eq!(
+            "1.23",
+            format!("{:.2}", "X".lazy_replace_opt("X", Some(1.23456)))
+        );
+        assert_eq!(
+            "a 1.2 b",
+            format!(
+                "{:.1}",
+                format_args!("a {} b", "X").replace_display("X", 1.23456)
+            )
+        );
+        assert_eq!(
+            "wo!wo",
+            format!(
+                "{:.2}",
+                "x!y".lazy_replace_at_ranges(&[0..1, 2..3], "world")
+            )
+        );
+    }
+
+    #[test]
+    fn replacement_ignores_the_width() {
+        assert_eq!("a+b", format!("{:>8}", "a-b".lazy_replace('-', "+")));
+        assert_eq!("a1b", format!("{:5}", "a-b".lazy_replace('-', 1)));
+        assert_eq!("a1.5b", format!("{:8.1}", "a-b".lazy_replace('-', 1.4999)));
+        assert_eq!(
+            "a+b",
+            format!("{:<8}", format_args!("a-b").replace_display("-", "+"))
+        );
+        assert_eq!(
+            "a+b",
+            format!("{:>8}", "a-b".lazy_replace_opt('-', Some("+")))
+        );
+    }
+
+    #[test]
+    fn try_lazy_replace() {
+        assert_eq!("a-b", "a b".try_lazy_replace(" ", "-").unwrap().to_string());
+        assert_eq!("", "".try_lazy_replace(" ", "-").unwrap().to_string());
+        assert_eq!(
+            ReplaceBuildError::EmptyNeedle,
+            "a b".try_lazy_replace("", "-").unwrap_err()
+        );
+    }
+
     #[test]
     fn replace_display() {
         assert_eq!(
@@ -276,4 +1111,236 @@ mod tests {
             .to_string()
         );
     }
+
+    #[test]
+    fn replace_display_across_writes() {
+        assert_eq!(
+            "[x]",
+            format_args!("{}{}", "!HE", "RE!")
+                .replace_display("!HERE!", "[x]")
+                .to_string()
+        );
+        assert_eq!(
+            "a[x]b[x]",
+            format_args!("{}{}{}{}", "a!", "H", "ERE!b!HE", "RE!")
+                .replace_display("!HERE!", "[x]")
+                .to_string()
+        );
+        // the held-back start of a match that never completes is written out at the end
+        assert_eq!(
+            "a!HER",
+            format_args!("{}{}", "a!H", "ER")
+                .replace_display("!HERE!", "[x]")
+                .to_string()
+        );
+        // after a false start, the match may begin inside what was held back
+        assert_eq!(
+            "aa[x]!",
+            format_args!("{}{}{}", "aa", "aab", "!")
+                .replace_display("aab", "[x]")
+                .to_string()
+        );
+        assert_eq!(
+            "[x]ab",
+            format_args!("{}{}{}", "ab", "ab", "ab")
+                .replace_display("abab", "[x]")
+                .to_string()
+        );
+        assert_eq!(
+            "é[x]é",
+            format_args!("{}{}{}", "éö", "→", "öé")
+                .replace_display("ö→ö", "[x]")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_writer_flush_threshold() {
+        #[derive(Default)]
+        struct CountingSink {
+            out: String,
+            writes: usize,
+        }
+
+        impl fmt::Write for CountingSink {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.out.push_str(s);
+                self.writes += 1;
+                Ok(())
+            }
+        }
+
+        let write_with_threshold = |threshold| {
+            let mut sink = CountingSink::default();
+            let mut writer =
+                ReplaceWriter::with_flush_threshold(&mut sink, "cat", "dog", threshold);
+            for chunk in &["a c", "at, a cat", " and ", "a hat", "", " ca", "t"] {
+                writer.write_str(chunk).unwrap();
+            }
+            writer.finish().unwrap();
+            sink
+        };
+
+        let unbuffered = write_with_threshold(0);
+        let buffered = write_with_threshold(8);
+        let collected = write_with_threshold(1000);
+
+        assert_eq!("a dog, a dog and a hat dog", unbuffered.out);
+        assert_eq!(unbuffered.out, buffered.out);
+        assert_eq!(unbuffered.out, collected.out);
+        assert!(buffered.writes < unbuffered.writes);
+        assert_eq!(1, collected.writes);
+    }
+
+    #[test]
+    fn replace_writer_dropped_without_finish() {
+        let mut unbuffered = String::new();
+        let mut writer = ReplaceWriter::new(&mut unbuffered, "cat", "dog");
+        writer.write_str("a cat, a ca").unwrap();
+        drop(writer);
+
+        // the possible start of a match is lost
+        assert_eq!("a dog, a ", unbuffered);
+
+        let mut buffered = String::new();
+        let mut writer = ReplaceWriter::with_flush_threshold(&mut buffered, "cat", "dog", 1000);
+        writer.write_str("a cat, a ca").unwrap();
+        drop(writer);
+
+        // and so is the buffer
+        assert_eq!("", buffered);
+    }
+
+    #[test]
+    fn replace_display_empty_needle() {
+        assert_eq!(
+            "ab".replace("", "-"),
+            format_args!("{}{}", "a", "b")
+                .replace_display("", "-")
+                .to_string()
+        );
+        assert_eq!("-", "".replace_display("", "-").to_string());
+    }
+
+    #[test]
+    fn empty_needle_matches_std() {
+        let haystacks = [
+            "",
+            "a",
+            "é",
+            "日",
+            "🦀",
+            "ab",
+            "aé",
+            "日本語",
+            "🦀x🦀",
+            "e\u{301}",
+            "\r\n",
+            " a b ",
+            "\0",
+        ];
+        let replacements = ["", "-", "xy", "é", "\u{FFFC}"];
+
+        for haystack in haystacks.iter() {
+            for replacement in replacements.iter() {
+                let expected = haystack.replace("", replacement);
+
+                assert_eq!(
+                    expected,
+                    haystack.lazy_replace("", replacement).to_string(),
+                    "lazy_replace on {:?} with {:?}",
+                    haystack,
+                    replacement
+                );
+                assert_eq!(
+                    expected,
+                    format_args!("{}", haystack)
+                        .replace_display("", replacement)
+                        .to_string(),
+                    "replace_display on {:?} with {:?}",
+                    haystack,
+                    replacement
+                );
+            }
+
+            assert_eq!(
+                haystack.replace("", "-"),
+                haystack.lazy_replace("", '-').to_string()
+            );
+
+            // the writer sees the haystack in pieces, split at every character boundary in turn
+            for (i, _) in haystack.char_indices() {
+                let (a, b) = haystack.split_at(i);
+                assert_eq!(
+                    haystack.replace("", "-"),
+                    format_args!("{}{}{}", a, "", b)
+                        .replace_display("", "-")
+                        .to_string(),
+                    "replace_display on {:?} split at {}",
+                    haystack,
+                    i
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn as_field() {
+        use std::{cell::Cell, sync::Mutex};
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Level, Metadata, Subscriber,
+        };
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Visit for &Recorder {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                if field.name() == "text" {
+                    self.0.lock().unwrap().push(format!("{:?}", value));
+                }
+            }
+        }
+
+        impl Subscriber for &'static Recorder {
+            fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+                *metadata.level() <= Level::INFO
+            }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut &**self);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        struct Counted<'c>(&'c Cell<usize>);
+
+        impl fmt::Display for Counted<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.set(self.0.get() + 1);
+                f.write_str("world")
+            }
+        }
+
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        let rendered = Cell::new(0);
+        let replaced = "hello, name".lazy_replace("name", Counted(&rendered));
+
+        tracing::subscriber::with_default(recorder, || {
+            tracing::debug!(text = replaced.as_field());
+            assert_eq!(0, rendered.get());
+
+            tracing::info!(text = replaced.as_field());
+            assert_eq!(1, rendered.get());
+        });
+
+        assert_eq!(vec!["hello, world"], *recorder.0.lock().unwrap());
+    }
 }
@@ -0,0 +1,579 @@
+//! Line-oriented transforms.
+
+use crate::{pattern::Pattern, write_matches, write_replacement, ReplacedString};
+use std::{
+    fmt::{self, Write},
+    ops::Range,
+};
+
+/// The separator written between a line number and the line itself by [`NumberedLines`]
+pub const LINE_NUMBER_SEPARATOR: &str = " | ";
+
+/// A type to lazily prefix every line of any type that implements `Display` with its line number.
+///
+/// Numbers are right-aligned, by default to the width of the largest one, and separated from the
+/// line by [`LINE_NUMBER_SEPARATOR`]. A trailing newline doesn't start a new line, so `"a\nb"` and
+/// `"a\nb\n"` both have two numbered lines, and an empty string has none.
+///
+/// Numbers stop at `usize::MAX`: if there are more lines than there are numbers left after
+/// `start`, the remaining lines are all numbered `usize::MAX`.
+///
+/// The width of the largest number depends on how many lines there are, which is only known once
+/// all of them have been written, but the first number is written before its line. So unless a
+/// width is given up front with [`with_width`](NumberedLines::with_width), the inner value is
+/// formatted twice for every render, once to count its lines and once to actually write them, and
+/// it must write the same text both times. With a width, it is formatted once and streamed through
+/// line by line.
+pub struct NumberedLines<D> {
+    inner: D,
+    start: usize,
+    width: Option<usize>,
+}
+
+impl<D> NumberedLines<D> {
+    /// Create a new instance of this type, numbering the first line with `start`
+    pub fn new(inner: D, start: usize) -> Self {
+        NumberedLines {
+            inner,
+            start,
+            width: None,
+        }
+    }
+
+    /// Create a new instance of this type, numbering the first line with `start` and right-aligning
+    /// the numbers to `width` columns, so the inner value only needs to be formatted once. Numbers
+    /// that don't fit take up as many columns as they need.
+    pub fn with_width(inner: D, start: usize, width: usize) -> Self {
+        NumberedLines {
+            inner,
+            start,
+            width: Some(width),
+        }
+    }
+}
+
+impl<D> fmt::Display for NumberedLines<D>
+where
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = match self.width {
+            Some(width) => width,
+            None => {
+                let mut counter = LineCounter::default();
+                write!(counter, "{}", self.inner)?;
+
+                let lines = counter.lines();
+                if lines == 0 {
+                    return Ok(());
+                }
+
+                self.start.saturating_add(lines - 1).to_string().len()
+            }
+        };
+
+        write!(
+            LineNumberWriter {
+                writer: f,
+                next: self.start,
+                width,
+                at_line_start: true,
+            },
+            "{}",
+            self.inner
+        )
+    }
+}
+
+#[derive(Default)]
+struct LineCounter {
+    newlines: usize,
+    in_line: bool,
+}
+
+impl LineCounter {
+    fn lines(&self) -> usize {
+        self.newlines + self.in_line as usize
+    }
+}
+
+impl fmt::Write for LineCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(last) = s.chars().last() {
+            self.newlines += s.matches('\n').count();
+            self.in_line = last != '\n';
+        }
+
+        Ok(())
+    }
+}
+
+struct LineNumberWriter<W> {
+    writer: W,
+    next: usize,
+    width: usize,
+    at_line_start: bool,
+}
+
+impl<W> fmt::Write for LineNumberWriter<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for line in s.split_inclusive('\n') {
+            if self.at_line_start {
+                write!(
+                    self.writer,
+                    "{:>width$}{}",
+                    self.next,
+                    LINE_NUMBER_SEPARATOR,
+                    width = self.width
+                )?;
+                self.next = self.next.saturating_add(1);
+            }
+
+            self.writer.write_str(line)?;
+            self.at_line_start = line.ends_with('\n');
+        }
+
+        Ok(())
+    }
+}
+
+/// See [`LazyReplaceDisplay::render_flattened`](crate::LazyReplaceDisplay::render_flattened)
+pub(crate) fn flattened<D>(inner: &D, join: &str) -> String
+where
+    D: fmt::Display + ?Sized,
+{
+    let mut flattener = Flattener {
+        writer: String::new(),
+        join,
+        started: false,
+        pending_breaks: 0,
+        after_cr: false,
+    };
+
+    write!(flattener, "{}", inner)
+        .expect("a Display implementation returned an error unexpectedly");
+
+    flattener.writer
+}
+
+/// Replaces line breaks between pieces of text with `join`. `\r\n` counts as a single break, and
+/// breaks before the first or after the last piece of text are dropped rather than replaced. The
+/// latter is why breaks are only counted, and written once more text arrives.
+struct Flattener<'j, W> {
+    writer: W,
+    join: &'j str,
+    started: bool,
+    pending_breaks: usize,
+    after_cr: bool,
+}
+
+impl<'j, W> fmt::Write for Flattener<'j, W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+
+        while !rest.is_empty() {
+            let text_len = rest.find(['\r', '\n']).unwrap_or(rest.len());
+
+            if text_len > 0 {
+                if self.started {
+                    for _ in 0..self.pending_breaks {
+                        self.writer.write_str(self.join)?;
+                    }
+                }
+
+                self.writer.write_str(&rest[..text_len])?;
+                self.started = true;
+                self.pending_breaks = 0;
+                self.after_cr = false;
+                rest = &rest[text_len..];
+            }
+
+            if let Some(c) = rest.chars().next() {
+                if !(c == '\n' && self.after_cr) {
+                    self.pending_breaks += 1;
+                }
+
+                self.after_cr = c == '\r';
+                rest = &rest[1..];
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A lazily-replaced string where the replacement only happens inside a byte range of the
+/// haystack. Everything outside the range is left as it is, and matches can't extend past the
+/// range's ends.
+pub struct ReplacedWithin<'a, P, R> {
+    haystack: &'a str,
+    range: Range<usize>,
+    needle: P,
+    replacement: R,
+}
+
+impl<'a, P, R> ReplacedWithin<'a, P, R> {
+    /// Create a new instance of this type
+    ///
+    /// # Panics
+    ///
+    /// Panics if either end of `range` is out of bounds or isn't on a `char` boundary.
+    pub fn new(haystack: &'a str, range: Range<usize>, needle: P, replacement: R) -> Self {
+        assert!(
+            range.start <= range.end && haystack.get(range.clone()).is_some(),
+            "range {:?} is not a valid slice of the haystack",
+            range
+        );
+
+        ReplacedWithin {
+            haystack,
+            range,
+            needle,
+            replacement,
+        }
+    }
+
+    /// Create a new instance of this type that only replaces inside the first line of `haystack`
+    pub fn first_line(haystack: &'a str, needle: P, replacement: R) -> Self {
+        let end = haystack.find('\n').unwrap_or(haystack.len());
+
+        ReplacedWithin::new(haystack, 0..end, needle, replacement)
+    }
+
+    /// Create a new instance of this type that only replaces inside the last line of `haystack`.
+    /// As with `str::lines`, a trailing newline doesn't start an extra, empty line.
+    pub fn last_line(haystack: &'a str, needle: P, replacement: R) -> Self {
+        let end = if haystack.ends_with('\n') {
+            haystack.len() - 1
+        } else {
+            haystack.len()
+        };
+        let start = haystack[..end].rfind('\n').map_or(0, |i| i + 1);
+
+        ReplacedWithin::new(haystack, start..end, needle, replacement)
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedWithin<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.haystack[..self.range.start])?;
+        fmt::Display::fmt(
+            &ReplacedString::new(
+                &self.haystack[self.range.clone()],
+                self.needle.clone(),
+                &self.replacement,
+            ),
+            f,
+        )?;
+        f.write_str(&self.haystack[self.range.end..])
+    }
+}
+
+/// A lazily-replaced string where a match is only replaced if it starts at one of the given
+/// columns of its line. Columns count characters from the start of the line, starting at 0, and
+/// other matches are left as they are.
+pub struct ReplacedAtColumns<'a, 'c, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: R,
+    columns: &'c [usize],
+}
+
+impl<'a, 'c, P, R> ReplacedAtColumns<'a, 'c, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: R, columns: &'c [usize]) -> Self {
+        ReplacedAtColumns {
+            haystack,
+            needle,
+            replacement,
+            columns,
+        }
+    }
+}
+
+impl<'a, 'c, P, R> fmt::Display for ReplacedAtColumns<'a, 'c, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cursor = ColumnCursor::default();
+
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            let column = cursor.advance_to(self.haystack, range.start);
+            cursor.advance_to(self.haystack, range.end);
+
+            if self.columns.contains(&column) {
+                write_replacement(f, &self.replacement)
+            } else {
+                f.write_str(&self.haystack[range])
+            }
+        })
+    }
+}
+
+/// A lazily-replaced string where matches are replaced with `even` or `odd`, depending on whether
+/// the line they start on has an even or odd number, for zebra striping. Lines are numbered from 1,
+/// so the first line is odd.
+pub struct ReplacedByLineParity<'a, P, E, O> {
+    haystack: &'a str,
+    needle: P,
+    even: E,
+    odd: O,
+}
+
+impl<'a, P, E, O> ReplacedByLineParity<'a, P, E, O> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, even: E, odd: O) -> Self {
+        ReplacedByLineParity {
+            haystack,
+            needle,
+            even,
+            odd,
+        }
+    }
+}
+
+impl<'a, P, E, O> fmt::Display for ReplacedByLineParity<'a, P, E, O>
+where
+    P: Pattern<'a> + Clone,
+    E: fmt::Display,
+    O: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cursor = ColumnCursor::default();
+
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            cursor.advance_to(self.haystack, range.start);
+
+            // `cursor.line` counts from 0, so the first line is at an even index
+            if cursor.line % 2 == 1 {
+                write_replacement(f, &self.even)
+            } else {
+                write_replacement(f, &self.odd)
+            }
+        })
+    }
+}
+
+/// Keeps track of the line and column at a byte position in a string, both counted from 0, which
+/// can only move forwards
+#[derive(Default)]
+struct ColumnCursor {
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl ColumnCursor {
+    /// Move to `position` in `haystack`, returning the column there
+    fn advance_to(&mut self, haystack: &str, position: usize) -> usize {
+        for c in haystack[self.position..position].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.position = position;
+        self.column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LazyReplace, LazyReplaceDisplay, ReplacedWithin};
+    use std::{cell::Cell, fmt};
+
+    /// Counts how many times it is rendered
+    struct Counted<'c>(&'c Cell<usize>, &'static str);
+
+    impl fmt::Display for Counted<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            f.write_str(self.1)
+        }
+    }
+
+    #[test]
+    fn number_lines() {
+        assert_eq!(
+            "1 | one\n2 | two\n3 | three",
+            "one\ntwo\nthree".lazy_number_lines(1).to_string()
+        );
+        assert_eq!(
+            " 9 | a\n10 | b\n",
+            "a\nb\n".lazy_number_lines(9).to_string()
+        );
+        assert_eq!("0 | \n1 | x", "\nx".lazy_number_lines(0).to_string());
+        assert_eq!("", "".lazy_number_lines(1).to_string());
+    }
+
+    #[test]
+    fn number_lines_with_width() {
+        let renders = Cell::new(0);
+        let counted = Counted(&renders, "a\nb\n");
+
+        assert_eq!(
+            "  9 | a\n 10 | b\n",
+            counted.lazy_number_lines_with_width(9, 3).to_string()
+        );
+        assert_eq!(1, renders.get());
+
+        assert_eq!(" 9 | a\n10 | b\n", counted.lazy_number_lines(9).to_string());
+        assert_eq!(3, renders.get());
+
+        assert_eq!(
+            "9 | a\n10 | b",
+            "a\nb".lazy_number_lines_with_width(9, 0).to_string()
+        );
+        assert_eq!("", "".lazy_number_lines_with_width(1, 4).to_string());
+    }
+
+    #[test]
+    fn number_lines_up_to_the_last_number() {
+        let max = usize::MAX.to_string();
+
+        assert_eq!(
+            format!("{} | a", max),
+            "a".lazy_number_lines(usize::MAX).to_string()
+        );
+        assert_eq!(
+            format!("{} | a\n{} | b\n{} | c", usize::MAX - 1, max, max),
+            "a\nb\nc".lazy_number_lines(usize::MAX - 1).to_string()
+        );
+    }
+
+    #[test]
+    fn number_replaced_lines() {
+        assert_eq!(
+            " 8 | a\n 9 | b\n10 | c",
+            "a,b,c"
+                .lazy_replace(",", "\n")
+                .lazy_number_lines(8)
+                .to_string()
+        );
+        assert_eq!(
+            "1 | foo\n2 | bar",
+            format_args!("{}{}", "fo", "o\nbar")
+                .lazy_number_lines(1)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_first_and_last_line() {
+        let text = "x top x\nx middle x\nx bottom x";
+
+        assert_eq!(
+            "_ top _\nx middle x\nx bottom x",
+            text.lazy_replace_first_line('x', '_').to_string()
+        );
+        assert_eq!(
+            "x top x\nx middle x\n_ bottom _",
+            text.lazy_replace_last_line('x', '_').to_string()
+        );
+        assert_eq!(
+            "x\n_\n",
+            "x\nx\n".lazy_replace_last_line('x', '_').to_string()
+        );
+        assert_eq!("_", "x".lazy_replace_first_line('x', '_').to_string());
+        assert_eq!("_", "x".lazy_replace_last_line('x', '_').to_string());
+    }
+
+    #[test]
+    fn matches_do_not_cross_the_range() {
+        assert_eq!(
+            "ab\nab",
+            "ab\nab".lazy_replace_first_line("b\na", "_").to_string()
+        );
+        assert_eq!(
+            "a_d",
+            ReplacedWithin::new("abcd", 1..3, "bc", "_").to_string()
+        );
+        assert_eq!(
+            "abcd",
+            ReplacedWithin::new("abcd", 1..2, "bc", "_").to_string()
+        );
+    }
+
+    #[test]
+    fn render_flattened() {
+        assert_eq!(
+            "one, two, three",
+            "one\ntwo\r\nthree\n".render_flattened(", ")
+        );
+        assert_eq!(
+            "a b  c",
+            format_args!("{}{}", "\na\r", "\nb\n\nc\r\n").render_flattened(" ")
+        );
+        assert_eq!(
+            "key: value; other: thing",
+            "key=value\nother=thing"
+                .lazy_replace('=', ": ")
+                .render_flattened("; ")
+        );
+        assert_eq!(
+            "first - last",
+            "first;last"
+                .lazy_replace(';', "\r\n")
+                .render_flattened(" - ")
+        );
+    }
+
+    #[test]
+    fn replace_at_columns() {
+        let text = "x x x\nx  x\näx xx";
+
+        assert_eq!(
+            "_ x _\n_  _\näx __",
+            text.lazy_replace_at_columns('x', '_', &[0, 3, 4])
+                .to_string()
+        );
+        assert_eq!(
+            text,
+            text.lazy_replace_at_columns('x', '_', &[]).to_string()
+        );
+        assert_eq!(
+            "a_b",
+            "ab\nab"
+                .lazy_replace_at_columns("b\na", '_', &[1])
+                .to_string()
+        );
+        assert_eq!(
+            "ab\n_",
+            "ab\nb"
+                .lazy_replace_at_columns(|c| c == 'b' || c == '\n', '_', &[0])
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_by_line_parity() {
+        assert_eq!(
+            "[o] one\n[e] two [e]\n[o] three\n\n[o] five",
+            "* one\n* two *\n* three\n\n* five"
+                .lazy_replace_by_line_parity('*', "[e]", "[o]")
+                .to_string()
+        );
+        // a match spanning a line break counts for the line it starts on
+        assert_eq!(
+            "a\nbE",
+            "a\nbc\nd"
+                .lazy_replace_by_line_parity("c\nd", 'E', 'O')
+                .to_string()
+        );
+    }
+}
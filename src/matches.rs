@@ -0,0 +1,1004 @@
+//! Replacements that are computed from each match, rather than being fixed up front.
+
+use crate::{pattern::Pattern, write_matches, write_replacement, ReplacedString};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt::{self, Write},
+};
+
+/// A lazily-replaced string where each replacement is the `Cow` returned by calling a closure with
+/// the matched text. A closure that only changes some matches can return the others borrowed, in
+/// which case nothing is allocated for them and the slice is written as it is, without the
+/// formatter's precision. Owned replacements get the precision, as usual.
+pub struct ReplacedCow<'a, P, F> {
+    haystack: &'a str,
+    needle: P,
+    f: F,
+}
+
+impl<'a, P, F> ReplacedCow<'a, P, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, f: F) -> Self {
+        ReplacedCow {
+            haystack,
+            needle,
+            f,
+        }
+    }
+}
+
+impl<'a, P, F> fmt::Display for ReplacedCow<'a, P, F>
+where
+    P: Pattern<'a> + Clone,
+    F: Fn(&str) -> Cow<'_, str>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            match (self.f)(matched) {
+                Cow::Borrowed(slice) => OrKept::Kept(slice),
+                Cow::Owned(replacement) => OrKept::Replaced(replacement),
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each replacement is computed from an accumulator that is carried
+/// from one match to the next, such as a running total.
+///
+/// The accumulator is reset to a clone of its initial value at the start of every render, so
+/// formatting the same value twice gives the same output both times. Because `Display::fmt` only
+/// gets `&self`, the closure is kept in a `RefCell`, which means the closure must not render the
+/// `ReplacedScan` it belongs to.
+pub struct ReplacedScan<'a, P, S, F> {
+    haystack: &'a str,
+    needle: P,
+    init: S,
+    f: RefCell<F>,
+}
+
+impl<'a, P, S, F> ReplacedScan<'a, P, S, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, init: S, f: F) -> Self {
+        ReplacedScan {
+            haystack,
+            needle,
+            init,
+            f: RefCell::new(f),
+        }
+    }
+}
+
+impl<'a, P, S, F, D> fmt::Display for ReplacedScan<'a, P, S, F>
+where
+    P: Pattern<'a> + Clone,
+    S: Clone,
+    F: FnMut(&mut S, &str) -> D,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut state = self.init.clone();
+        let mut scan = self.f.borrow_mut();
+
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            scan(&mut state, matched)
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each replacement is the result of calling a closure with the
+/// 0-based index of the match and the matched text, which makes it possible to, for example,
+/// uppercase every other match.
+///
+/// This is the most general of the closure-based replacements, which the narrower ones are built
+/// on. The closure is called again, with indices starting from 0 again, on every render. Because
+/// `Display::fmt` only gets `&self`, it is kept in a `RefCell`, so it must not render the
+/// `ReplacedIndexedWith` it belongs to.
+pub struct ReplacedIndexedWith<'a, P, F> {
+    haystack: &'a str,
+    needle: P,
+    f: RefCell<F>,
+}
+
+impl<'a, P, F> ReplacedIndexedWith<'a, P, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, f: F) -> Self {
+        ReplacedIndexedWith {
+            haystack,
+            needle,
+            f: RefCell::new(f),
+        }
+    }
+}
+
+impl<'a, P, F, D> fmt::Display for ReplacedIndexedWith<'a, P, F>
+where
+    P: Pattern<'a> + Clone,
+    F: FnMut(usize, &'a str) -> D,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut replace = self.f.borrow_mut();
+
+        write_matches(f, self.haystack, self.needle.clone(), |f, i, range| {
+            write_replacement(f, &replace(i, &self.haystack[range]))
+        })
+    }
+}
+
+/// A lazily-replaced string where each replacement is the result of calling a closure with the
+/// matched text. The closure is called again on every render.
+pub struct ReplacedWith<'a, P, F> {
+    haystack: &'a str,
+    needle: P,
+    f: F,
+}
+
+impl<'a, P, F> ReplacedWith<'a, P, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, f: F) -> Self {
+        ReplacedWith {
+            haystack,
+            needle,
+            f,
+        }
+    }
+}
+
+impl<'a, P, F, D> fmt::Display for ReplacedWith<'a, P, F>
+where
+    P: Pattern<'a> + Clone,
+    F: Fn(&str) -> D,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &str| {
+            (self.f)(matched)
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each match is replaced with the result of calling a closure
+/// without arguments, such as one returning the current time.
+///
+/// The closure is called once per match, every time the value is rendered, and never when it is
+/// created, so something like a timestamp always reflects the time of rendering. Calling the
+/// closure once per match rather than once per render means that the replacements in a single
+/// render can differ.
+pub struct ReplacedTimestamp<'a, P, F> {
+    haystack: &'a str,
+    needle: P,
+    f: F,
+}
+
+impl<'a, P, F> ReplacedTimestamp<'a, P, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, f: F) -> Self {
+        ReplacedTimestamp {
+            haystack,
+            needle,
+            f,
+        }
+    }
+}
+
+impl<'a, P, F, D> fmt::Display for ReplacedTimestamp<'a, P, F>
+where
+    P: Pattern<'a> + Clone,
+    F: Fn() -> D,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, _: &str| (self.f)()).fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each match of a placeholder is replaced with the plural form of
+/// a word that fits a count, as chosen by a rule function, like `"item"` for 1 and `"items"` for
+/// anything else in English.
+///
+/// The rule is called once per render, and its result is used for every match. The count is
+/// passed to the rule as it is, so the rule decides what negative counts need.
+pub struct ReplacedPlural<'a, P, F> {
+    haystack: &'a str,
+    needle: P,
+    count: i64,
+    rule: F,
+}
+
+impl<'a, P, F> ReplacedPlural<'a, P, F> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, count: i64, rule: F) -> Self {
+        ReplacedPlural {
+            haystack,
+            needle,
+            count,
+            rule,
+        }
+    }
+}
+
+impl<'a, P, F, D> fmt::Display for ReplacedPlural<'a, P, F>
+where
+    P: Pattern<'a> + Clone,
+    F: Fn(i64) -> D,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(
+            &ReplacedString::new(self.haystack, self.needle.clone(), (self.rule)(self.count)),
+            f,
+        )
+    }
+}
+
+/// A lazily-replaced string with an optional replacement. Where the replacement is `None`, each
+/// match is left as it was, rather than being removed.
+pub struct ReplacedOpt<'a, P, R> {
+    haystack: &'a str,
+    needle: P,
+    replacement: Option<R>,
+}
+
+impl<'a, P, R> ReplacedOpt<'a, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, replacement: Option<R>) -> Self {
+        ReplacedOpt {
+            haystack,
+            needle,
+            replacement,
+        }
+    }
+}
+
+impl<'a, P, R> fmt::Display for ReplacedOpt<'a, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            match self.replacement {
+                Some(ref replacement) => OrKept::Replaced(replacement),
+                None => OrKept::Kept(matched),
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where the first match is replaced with `first`, the second with
+/// `second` and so on. Past `tenth`, numbers with an ordinal suffix are used instead, like `11th`
+/// and `22nd`.
+pub struct ReplacedOrdinal<'a, P> {
+    haystack: &'a str,
+    needle: P,
+}
+
+impl<'a, P> ReplacedOrdinal<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P) -> Self {
+        ReplacedOrdinal { haystack, needle }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedOrdinal<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |i, _: &str| {
+            Ordinal(i + 1)
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where the first match is replaced with the first of the values, the
+/// second with the second and so on. Once the values run out, the remaining matches are left as
+/// they are; the values don't wrap around. Values that there are no matches for are ignored.
+pub struct ReplacedByOrder<'a, 'v, P, R> {
+    haystack: &'a str,
+    needle: P,
+    values: &'v [R],
+}
+
+impl<'a, 'v, P, R> ReplacedByOrder<'a, 'v, P, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, values: &'v [R]) -> Self {
+        ReplacedByOrder {
+            haystack,
+            needle,
+            values,
+        }
+    }
+}
+
+impl<'a, 'v, P, R> fmt::Display for ReplacedByOrder<'a, 'v, P, R>
+where
+    P: Pattern<'a> + Clone,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |i, matched: &'a str| {
+            match self.values.get(i) {
+                Some(value) => OrKept::Replaced(value),
+                None => OrKept::Kept(matched),
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each match is replaced by a template, in which `{}` stands for
+/// the matched text. `{{` and `}}` stand for literal braces, and any other brace is written as it
+/// is.
+pub struct ReplacedFormat<'a, 't, P> {
+    haystack: &'a str,
+    needle: P,
+    template: &'t str,
+}
+
+impl<'a, 't, P> ReplacedFormat<'a, 't, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, template: &'t str) -> Self {
+        ReplacedFormat {
+            haystack,
+            needle,
+            template,
+        }
+    }
+}
+
+impl<'a, 't, P> fmt::Display for ReplacedFormat<'a, 't, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            Templated {
+                template: self.template,
+                matched,
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// A [`ReplacedFormat`] template filled in with one match
+struct Templated<'t, 'm> {
+    template: &'t str,
+    matched: &'m str,
+}
+
+impl<'t, 'm> fmt::Display for Templated<'t, 'm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.template;
+
+        while let Some(i) = rest.find(['{', '}']) {
+            f.write_str(&rest[..i])?;
+            rest = &rest[i..];
+
+            let len = if rest.starts_with("{}") {
+                f.write_str(self.matched)?;
+                2
+            } else if rest.starts_with("{{") || rest.starts_with("}}") {
+                f.write_str(&rest[..1])?;
+                2
+            } else {
+                f.write_str(&rest[..1])?;
+                1
+            };
+
+            rest = &rest[len..];
+        }
+
+        f.write_str(rest)
+    }
+}
+
+/// A replacement, or text to keep as it is, like a match that isn't replaced. Kept text is written
+/// without the formatter's precision.
+enum OrKept<'m, R> {
+    Replaced(R),
+    Kept(&'m str),
+}
+
+impl<'m, R> fmt::Display for OrKept<'m, R>
+where
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OrKept::Replaced(ref replacement) => fmt::Display::fmt(replacement, f),
+            OrKept::Kept(matched) => f.write_str(matched),
+        }
+    }
+}
+
+const ORDINAL_WORDS: [&str; 10] = [
+    "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+];
+
+/// A 1-based ordinal, as a word where there is one in `ORDINAL_WORDS` and otherwise as a number
+/// with a suffix
+struct Ordinal(usize);
+
+impl fmt::Display for Ordinal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = self.0;
+
+        if let Some(word) = n.checked_sub(1).and_then(|i| ORDINAL_WORDS.get(i)) {
+            return f.write_str(word);
+        }
+
+        let suffix = match (n % 10, n % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+
+        write!(f, "{}{}", n, suffix)
+    }
+}
+
+/// A lazily-replaced string where the matches are replaced with the labels `a`, `b`, ..., `z`,
+/// then `aa`, `ab` and so on, like footnotes or spreadsheet columns.
+pub struct ReplacedAlpha<'a, P> {
+    haystack: &'a str,
+    needle: P,
+}
+
+impl<'a, P> ReplacedAlpha<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P) -> Self {
+        ReplacedAlpha { haystack, needle }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedAlpha<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |i, _: &str| {
+            AlphaLabel(i)
+        })
+        .fmt(f)
+    }
+}
+
+/// A lazily-replaced string where each match is replaced with a run of `fill`, as long as the
+/// width requested from the formatter, like `{:40}`. Without a width, each run is as long as the
+/// match it replaces, counted in characters.
+///
+/// Only the replacements take up the width: the text between matches is written as it is.
+pub struct ReplacedFill<'a, P> {
+    haystack: &'a str,
+    needle: P,
+    fill: char,
+}
+
+impl<'a, P> ReplacedFill<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, fill: char) -> Self {
+        ReplacedFill {
+            haystack,
+            needle,
+            fill,
+        }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedFill<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = f.width();
+
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            let len = width.unwrap_or_else(|| self.haystack[range].chars().count());
+            (0..len).try_for_each(|_| f.write_char(self.fill))
+        })
+    }
+}
+
+/// A lazily-replaced string where each match is masked by replacing each of its characters with
+/// `mask`, for redaction that keeps the layout of the text intact.
+///
+/// The mask is as long as the match in characters, not bytes, so `"né"` masked with `*` becomes
+/// `"**"`. Unlike [`ReplacedFill`], the formatter's width is ignored.
+pub struct ReplacedMask<'a, P> {
+    haystack: &'a str,
+    needle: P,
+    mask: char,
+}
+
+impl<'a, P> ReplacedMask<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, mask: char) -> Self {
+        ReplacedMask {
+            haystack,
+            needle,
+            mask,
+        }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedMask<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            Masked {
+                mask: self.mask,
+                matched,
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// One match masked by a [`ReplacedMask`]
+struct Masked<'m> {
+    mask: char,
+    matched: &'m str,
+}
+
+impl<'m> fmt::Display for Masked<'m> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.matched
+            .chars()
+            .try_for_each(|_| f.write_char(self.mask))
+    }
+}
+
+/// How [`ReplacedStrategy`] replaces each match, for when that is only known at runtime, such as
+/// from a configuration file
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReplaceStrategy {
+    /// Replace each match with this text
+    Literal(String),
+    /// Convert each match to uppercase, character by character
+    Uppercase,
+    /// Convert each match to lowercase, character by character
+    Lowercase,
+    /// Remove each match
+    Strip,
+    /// Keep each match, but put the first text in front of it and the second one behind it
+    Wrap(String, String),
+}
+
+/// A lazily-replaced string where each match is replaced according to a [`ReplaceStrategy`]
+/// chosen at runtime.
+///
+/// Case is converted one character at a time, using `char::to_uppercase` and
+/// `char::to_lowercase`, so nothing is allocated. Unlike `str::to_lowercase`, this always maps a
+/// capital sigma to `σ`, even at the end of a word.
+///
+/// Literal replacements are cut to the formatter's precision, like those of [`ReplacedString`].
+/// The other strategies ignore the precision.
+pub struct ReplacedStrategy<'a, P> {
+    haystack: &'a str,
+    needle: P,
+    strategy: ReplaceStrategy,
+}
+
+impl<'a, P> ReplacedStrategy<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, strategy: ReplaceStrategy) -> Self {
+        ReplacedStrategy {
+            haystack,
+            needle,
+            strategy,
+        }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedStrategy<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ReplacedIndexedWith::new(self.haystack, self.needle.clone(), |_, matched: &'a str| {
+            Strategized {
+                strategy: &self.strategy,
+                matched,
+            }
+        })
+        .fmt(f)
+    }
+}
+
+/// One match replaced by a [`ReplacedStrategy`]
+struct Strategized<'s, 'm> {
+    strategy: &'s ReplaceStrategy,
+    matched: &'m str,
+}
+
+impl<'s, 'm> fmt::Display for Strategized<'s, 'm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let matched = self.matched;
+
+        match *self.strategy {
+            ReplaceStrategy::Literal(ref replacement) => write_replacement(f, replacement),
+            ReplaceStrategy::Uppercase => matched
+                .chars()
+                .flat_map(char::to_uppercase)
+                .try_for_each(|c| f.write_char(c)),
+            ReplaceStrategy::Lowercase => matched
+                .chars()
+                .flat_map(char::to_lowercase)
+                .try_for_each(|c| f.write_char(c)),
+            ReplaceStrategy::Strip => Ok(()),
+            ReplaceStrategy::Wrap(ref before, ref after) => {
+                f.write_str(before)?;
+                f.write_str(matched)?;
+                f.write_str(after)
+            }
+        }
+    }
+}
+
+/// The 0-based index of a label, written in bijective base 26 with the digits `a` to `z`
+struct AlphaLabel(usize);
+
+impl fmt::Display for AlphaLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // enough for `usize::MAX`, with 64-bit `usize`s
+        let mut digits = [0u8; 14];
+        let mut start = digits.len();
+        let mut n = self.0 as u128 + 1;
+
+        while n > 0 {
+            n -= 1;
+            start -= 1;
+            digits[start] = b'a' + (n % 26) as u8;
+            n /= 26;
+        }
+
+        f.write_str(std::str::from_utf8(&digits[start..]).expect("labels are ASCII"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlphaLabel;
+    use crate::{needle_set, LazyReplace, ReplaceStrategy, StaticNeedleSet};
+    use std::{borrow::Cow, cell::Cell};
+
+    #[test]
+    fn replace_scan() {
+        let totals = "1, 2, 3 and 4".lazy_replace_scan(
+            |c: char| c.is_ascii_digit(),
+            0,
+            |total: &mut u32, digit| {
+                *total += digit.parse::<u32>().unwrap();
+                *total
+            },
+        );
+
+        assert_eq!("1, 3, 6 and 10", totals.to_string());
+        assert_eq!("1, 3, 6 and 10", totals.to_string());
+        assert_eq!(
+            "#0 #1 #2",
+            "# # #"
+                .lazy_replace_scan("#", 0, |n: &mut usize, m| {
+                    *n += 1;
+                    format!("{}{}", m, *n - 1)
+                })
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_opt() {
+        let greeting = "Hello, {name}!";
+
+        assert_eq!(
+            "Hello, world!",
+            greeting
+                .lazy_replace_opt("{name}", Some("world"))
+                .to_string()
+        );
+        assert_eq!(
+            "Hello, {name}!",
+            greeting
+                .lazy_replace_opt("{name}", None::<&str>)
+                .to_string()
+        );
+
+        // only replacements are cut to the precision, kept matches are written as they are
+        assert_eq!(
+            "Hello, w!",
+            format!("{:.1}", greeting.lazy_replace_opt("{name}", Some("world")))
+        );
+        assert_eq!(
+            "Hello, {name}!",
+            format!("{:.1}", greeting.lazy_replace_opt("{name}", None::<&str>))
+        );
+    }
+
+    #[test]
+    fn replace_cow() {
+        static SPELLINGS: StaticNeedleSet = needle_set!["Colour", "color", "COLOUR"];
+
+        let owned = Cell::new(0);
+        let replaced = "Colour, color and COLOUR".lazy_replace_cow(&SPELLINGS, |matched| {
+            if matched.contains(['u', 'U']) {
+                owned.set(owned.get() + 1);
+                Cow::Owned(matched.replace(['u', 'U'], ""))
+            } else {
+                Cow::Borrowed(matched)
+            }
+        });
+
+        assert_eq!("Color, color and COLOR", replaced.to_string());
+        assert_eq!(2, owned.get());
+        assert_eq!("Col, color and COL", format!("{:.3}", replaced));
+
+        assert_eq!(
+            "a b c",
+            "a b c"
+                .lazy_replace_cow(' ', |matched| Cow::Borrowed(matched))
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_plural() {
+        let english = |n: i64| if n == 1 { "item" } else { "items" };
+        let cart = |count| {
+            format!(
+                "{} {}",
+                count,
+                "{item} in your cart".lazy_replace_plural("{item}", count, english)
+            )
+        };
+
+        assert_eq!("1 item in your cart", cart(1));
+        assert_eq!("3 items in your cart", cart(3));
+        assert_eq!("0 items in your cart", cart(0));
+        assert_eq!("-1 items in your cart", cart(-1));
+
+        let calls = Cell::new(0);
+        let both = "{n} and {n}".lazy_replace_plural("{n}", 2, |n| {
+            calls.set(calls.get() + 1);
+            if n == 1 {
+                "one"
+            } else {
+                "many"
+            }
+        });
+        assert_eq!("many and many", both.to_string());
+        assert_eq!(1, calls.get());
+    }
+
+    #[test]
+    fn replace_by_order() {
+        assert_eq!(
+            "Dear Ada, your order 17 ships Monday.",
+            "Dear _, your order _ ships _."
+                .lazy_replace_by_order('_', &["Ada", "17", "Monday"])
+                .to_string()
+        );
+        assert_eq!(
+            "1 and 2",
+            "_ and _"
+                .lazy_replace_by_order('_', &[1, 2, 3, 4])
+                .to_string()
+        );
+        assert_eq!(
+            "1, 2, _, _",
+            "_, _, _, _".lazy_replace_by_order('_', &[1, 2]).to_string()
+        );
+        assert_eq!(
+            "_",
+            "_".lazy_replace_by_order::<_, u8>('_', &[]).to_string()
+        );
+    }
+
+    #[test]
+    fn replace_ordinal() {
+        assert_eq!(
+            "first, second, third, fourth, fifth",
+            "#, #, #, #, #".lazy_replace_ordinal('#').to_string()
+        );
+
+        let many = "# ".repeat(24).lazy_replace_ordinal('#').to_string();
+        let ordinals: Vec<_> = many.split_whitespace().collect();
+        assert_eq!(
+            &ordinals[8..],
+            &[
+                "ninth", "tenth", "11th", "12th", "13th", "14th", "15th", "16th", "17th", "18th",
+                "19th", "20th", "21st", "22nd", "23rd", "24th"
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_format() {
+        assert_eq!(
+            "<a>, <b>, <c>",
+            "a, b, c"
+                .lazy_replace_format(char::is_alphabetic, "<{}>")
+                .to_string()
+        );
+        assert_eq!(
+            "{x} = x, {{}}",
+            "x".lazy_replace_format('x', "{{{}}} = {}, {{{{}}}}")
+                .to_string()
+        );
+        assert_eq!("} { x", "x".lazy_replace_format('x', "} { {}").to_string());
+    }
+
+    #[test]
+    fn replace_with_string() {
+        struct Shout;
+
+        impl Shout {
+            fn to_shouted(&self, s: &str) -> String {
+                s.to_uppercase() + "!"
+            }
+        }
+
+        assert_eq!(
+            "HEY! you HEY!",
+            "hey you hey"
+                .lazy_replace_with_string("hey", |m| Shout.to_shouted(m))
+                .to_string()
+        );
+        assert_eq!(
+            "# ## ###",
+            "1 2 3"
+                .lazy_replace_with_string(
+                    |c: char| c.is_ascii_digit(),
+                    |m| { "#".repeat(m.parse().unwrap()) }
+                )
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_indexed_with() {
+        let mut seen = Vec::new();
+        let replaced = "one two three four"
+            .lazy_replace_indexed_with(
+                |c: char| c.is_alphabetic(),
+                |i, c: &str| {
+                    seen.push(i);
+                    if i % 2 == 0 {
+                        c.to_uppercase()
+                    } else {
+                        c.to_string()
+                    }
+                },
+            )
+            .to_string();
+        assert_eq!("OnE tWo ThReE fOuR", replaced);
+        assert_eq!((0..15).collect::<Vec<_>>(), seen);
+
+        assert_eq!(
+            "[0:a] [1:bb] [2:a]",
+            "a bb a"
+                .lazy_replace_indexed_with(&needle_set!["a", "bb"], |i, matched: &str| format!(
+                    "[{}:{}]",
+                    i, matched
+                ))
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_alpha() {
+        assert_eq!(
+            "see [a], [b] and [c]",
+            "see [?], [?] and [?]".lazy_replace_alpha('?').to_string()
+        );
+
+        let labels = "# ".repeat(30).lazy_replace_alpha('#').to_string();
+        let labels: Vec<_> = labels.split_whitespace().collect();
+        assert_eq!(&labels[24..], &["y", "z", "aa", "ab", "ac", "ad"]);
+
+        assert_eq!("zz", AlphaLabel(701).to_string());
+        assert_eq!("aaa", AlphaLabel(702).to_string());
+        assert_eq!("ab", AlphaLabel(27).to_string());
+    }
+
+    #[test]
+    fn replace_fill() {
+        let page = "Title\n----\nText\n----";
+
+        assert_eq!(
+            "Title\n==========\nText\n==========",
+            format!("{:10}", page.lazy_replace_fill("----", '='))
+        );
+        assert_eq!(
+            "Title\n====\nText\n====",
+            page.lazy_replace_fill("----", '=').to_string()
+        );
+        assert_eq!("a──b", format!("{}", "a→→b".lazy_replace_fill('→', '─')));
+        assert_eq!(
+            "ab",
+            format!("{:w$}", "a--b".lazy_replace_fill("--", '='), w = 0)
+        );
+    }
+
+    #[test]
+    fn replace_timestamp() {
+        let calls = Cell::new(0);
+        let line = "[!NOW!] started, [!NOW!] done".lazy_replace_timestamp("!NOW!", || {
+            calls.set(calls.get() + 1);
+            calls.get()
+        });
+
+        assert_eq!(0, calls.get());
+        assert_eq!("[1] started, [2] done", line.to_string());
+        assert_eq!("[3] started, [4] done", line.to_string());
+        assert_eq!(4, calls.get());
+    }
+
+    #[test]
+    fn replace_mask() {
+        static SECRETS: StaticNeedleSet = needle_set!["hunter2", "pässwörd", "🔑🔑"];
+
+        let line = "user: ferris, pass: pässwörd, old: hunter2, key: 🔑🔑";
+        let masked = line.lazy_replace_mask(&SECRETS, '*').to_string();
+
+        assert_eq!(
+            "user: ferris, pass: ********, old: *******, key: **",
+            masked
+        );
+        assert_eq!(line.chars().count(), masked.chars().count());
+        assert_eq!(
+            "#####",
+            format!("{:9}", "néééé".lazy_replace_mask("néééé", '#'))
+        );
+    }
+
+    #[test]
+    fn replace_strategy() {
+        static NAMES: StaticNeedleSet = needle_set!["ferris", "Straße"];
+
+        let render = |strategy| {
+            "hi ferris and Straße!"
+                .lazy_replace_strategy(&NAMES, strategy)
+                .to_string()
+        };
+
+        assert_eq!(
+            "hi _ and _!",
+            render(ReplaceStrategy::Literal("_".to_string()))
+        );
+        assert_eq!("hi FERRIS and STRASSE!", render(ReplaceStrategy::Uppercase));
+        assert_eq!("hi ferris and straße!", render(ReplaceStrategy::Lowercase));
+        assert_eq!("hi  and !", render(ReplaceStrategy::Strip));
+        assert_eq!(
+            "hi <b>ferris</b> and <b>Straße</b>!",
+            render(ReplaceStrategy::Wrap("<b>".to_string(), "</b>".to_string()))
+        );
+
+        assert_eq!(
+            "keep L x",
+            format!(
+                "{:.1}",
+                "keep LONG x"
+                    .lazy_replace_strategy("LONG", ReplaceStrategy::Literal("Long".to_string()))
+            )
+        );
+        assert_eq!(
+            "keep LONG x",
+            format!(
+                "{:.1}",
+                "keep long x".lazy_replace_strategy("long", ReplaceStrategy::Uppercase)
+            )
+        );
+    }
+}
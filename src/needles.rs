@@ -0,0 +1,1174 @@
+//! Patterns beyond the ones in [`pattern`](crate::pattern), such as several needles at once.
+
+use crate::{
+    pattern::{Pattern, SearchStep, Searcher},
+    write_replacement, write_with_precision,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{OnceLock, PoisonError, RwLock},
+};
+
+/// A fixed set of needles that can be built at compile time, for use as a pattern.
+///
+/// The needles are compiled into a trie, which is walked byte by byte from each position that one
+/// of them can start at, so finding the longest needle there takes one step per byte of the match,
+/// no matter how many needles there are. The [`needle_set!`](crate::needle_set) macro builds the
+/// trie in a `const`, so it can be used for a `static` or `const` whose set is built once, at
+/// compile time:
+///
+/// ```
+/// use lazy_string_replace::{needle_set, LazyReplace, StaticNeedleSet};
+///
+/// static ANIMALS: StaticNeedleSet = needle_set!["cat", "dog"];
+///
+/// assert_eq!(
+///     "my pet is a pet",
+///     "my cat is a dog".lazy_replace_set(&ANIMALS, "pet").to_string()
+/// );
+/// ```
+///
+/// [`new`](StaticNeedleSet::new) builds the same trie at runtime, in an allocation of its own.
+///
+/// Where several needles match at the same position, the longest one wins. Empty needles never
+/// match.
+#[derive(Clone, Debug)]
+pub struct StaticNeedleSet {
+    needles: &'static [&'static str],
+    trie: Cow<'static, [TrieNode]>,
+    first_bytes: [bool; 256],
+}
+
+/// A node of the trie in a [`StaticNeedleSet`]. This is only public so that
+/// [`needle_set!`](crate::needle_set) can build a trie in a `const`.
+///
+/// The root is the first node, and the other nodes are in breadth-first order, with the children
+/// of each node next to each other and sorted by the byte leading to them.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct TrieNode {
+    /// The byte on the edge from the parent to this node
+    byte: u8,
+    /// Whether a needle ends at this node
+    is_end: bool,
+    first_child: usize,
+    child_count: usize,
+    /// The index of a needle that starts with the bytes leading to this node
+    needle: usize,
+    depth: usize,
+}
+
+impl TrieNode {
+    const ROOT: TrieNode = TrieNode {
+        byte: 0,
+        is_end: false,
+        first_child: 0,
+        child_count: 0,
+        needle: 0,
+        depth: 0,
+    };
+}
+
+/// Whether `a` and `b` are both at least `len` bytes long and start with the same `len` bytes
+const fn same_prefix(a: &[u8], b: &[u8], len: usize) -> bool {
+    if a.len() < len || b.len() < len {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < len {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// The number of nodes in the trie for `needles`, which is one for each distinct non-empty prefix
+/// of theirs, plus the root
+#[doc(hidden)]
+pub const fn trie_len(needles: &[&str]) -> usize {
+    let mut len = 1;
+    let mut i = 0;
+
+    while i < needles.len() {
+        let needle = needles[i].as_bytes();
+        let mut prefix_len = 1;
+
+        while prefix_len <= needle.len() {
+            let mut seen = false;
+            let mut j = 0;
+            while j < i && !seen {
+                seen = same_prefix(needles[j].as_bytes(), needle, prefix_len);
+                j += 1;
+            }
+
+            if !seen {
+                len += 1;
+            }
+            prefix_len += 1;
+        }
+
+        i += 1;
+    }
+
+    len
+}
+
+/// Build the trie for `needles`, which has [`trie_len`] nodes
+#[doc(hidden)]
+pub const fn build_trie<const N: usize>(needles: &[&str]) -> [TrieNode; N] {
+    let mut nodes = [TrieNode::ROOT; N];
+    fill_trie(needles, &mut nodes);
+    nodes
+}
+
+/// Fill `nodes`, which must be exactly [`trie_len`] long, with the trie for `needles`
+const fn fill_trie(needles: &[&str], nodes: &mut [TrieNode]) {
+    assert!(nodes.len() == trie_len(needles));
+
+    let mut len = 1;
+    let mut parent = 0;
+
+    // the children of each node are added once all nodes before it have their children, which
+    // puts the nodes in breadth-first order
+    while parent < len {
+        let depth = nodes[parent].depth;
+        let prefix = needles_prefix(needles, nodes[parent].needle, depth);
+        nodes[parent].first_child = len;
+
+        // add a child for each byte that follows the parent's prefix in some needle, smallest first
+        let mut last_byte: Option<u8> = None;
+        loop {
+            let mut next: Option<(u8, usize)> = None;
+            let mut j = 0;
+
+            while j < needles.len() {
+                let needle = needles[j].as_bytes();
+
+                if needle.len() > depth && same_prefix(needle, prefix, depth) {
+                    let byte = needle[depth];
+                    let after_last = match last_byte {
+                        Some(last) => byte > last,
+                        None => true,
+                    };
+                    let before_next = match next {
+                        Some((next_byte, _)) => byte < next_byte,
+                        None => true,
+                    };
+
+                    if after_last && before_next {
+                        next = Some((byte, j));
+                    }
+                }
+
+                j += 1;
+            }
+
+            let (byte, needle) = match next {
+                Some(next) => next,
+                None => break,
+            };
+
+            let mut is_end = false;
+            let mut j = 0;
+            while j < needles.len() && !is_end {
+                let other = needles[j].as_bytes();
+                is_end = other.len() == depth + 1
+                    && same_prefix(other, needles[needle].as_bytes(), depth + 1);
+                j += 1;
+            }
+
+            nodes[len] = TrieNode {
+                byte,
+                is_end,
+                first_child: 0,
+                child_count: 0,
+                needle,
+                depth: depth + 1,
+            };
+            len += 1;
+            last_byte = Some(byte);
+        }
+
+        nodes[parent].child_count = len - nodes[parent].first_child;
+        parent += 1;
+    }
+}
+
+/// The first `depth` bytes of `needles[needle]`, or nothing at all for the root
+const fn needles_prefix<'n>(needles: &[&'n str], needle: usize, depth: usize) -> &'n [u8] {
+    if depth == 0 {
+        &[]
+    } else {
+        needles[needle].as_bytes()
+    }
+}
+
+/// The bytes the needles in `trie` can start with, which are those of the root's children
+const fn first_bytes(trie: &[TrieNode]) -> [bool; 256] {
+    let mut first_bytes = [false; 256];
+    let root = &trie[0];
+    let mut i = root.first_child;
+
+    while i < root.first_child + root.child_count {
+        first_bytes[trie[i].byte as usize] = true;
+        i += 1;
+    }
+
+    first_bytes
+}
+
+impl StaticNeedleSet {
+    /// Create a new instance of this type, building its trie at runtime. Use
+    /// [`needle_set!`](crate::needle_set) to build it at compile time instead.
+    pub fn new(needles: &'static [&'static str]) -> Self {
+        let mut trie = vec![TrieNode::ROOT; trie_len(needles)];
+        fill_trie(needles, &mut trie);
+
+        StaticNeedleSet {
+            needles,
+            first_bytes: first_bytes(&trie),
+            trie: Cow::Owned(trie),
+        }
+    }
+
+    /// Create a new instance of this type from a trie built by [`build_trie`], which is what
+    /// [`needle_set!`](crate::needle_set) does
+    #[doc(hidden)]
+    pub const fn from_trie(needles: &'static [&'static str], trie: &'static [TrieNode]) -> Self {
+        StaticNeedleSet {
+            needles,
+            trie: Cow::Borrowed(trie),
+            first_bytes: first_bytes(trie),
+        }
+    }
+
+    /// The needles in this set, in the order they were given
+    pub fn needles(&self) -> &'static [&'static str] {
+        self.needles
+    }
+
+    /// The length of the longest needle matching at the very start of `haystack`, if there is one
+    fn longest_at(&self, haystack: &[u8]) -> Option<usize> {
+        let mut node = &self.trie[0];
+        let mut longest = None;
+
+        for &byte in haystack {
+            let children = &self.trie[node.first_child..node.first_child + node.child_count];
+            node = match children.binary_search_by_key(&byte, |child| child.byte) {
+                Ok(i) => &children[i],
+                Err(_) => break,
+            };
+
+            if node.is_end {
+                longest = Some(node.depth);
+            }
+        }
+
+        longest
+    }
+
+    /// Find the leftmost match at or after `from`, returning its range
+    fn find_from(&self, haystack: &str, from: usize) -> Option<(usize, usize)> {
+        let bytes = haystack.as_bytes();
+
+        // needles start with a whole character, so they can only match at character boundaries
+        bytes[from..]
+            .iter()
+            .enumerate()
+            .filter(|&(_, &byte)| self.first_bytes[byte as usize])
+            .find_map(|(i, _)| {
+                let start = from + i;
+                self.longest_at(&bytes[start..])
+                    .map(|len| (start, start + len))
+            })
+    }
+}
+
+/// Construct a [`StaticNeedleSet`] from a list of string literals, building its trie in a `const`.
+/// This can be used in `const` and `static` items, so that the set is built at compile time.
+#[macro_export]
+macro_rules! needle_set {
+    ($($needle:expr),* $(,)?) => {{
+        const NEEDLES: &[&str] = &[$($needle),*];
+        const TRIE: [$crate::TrieNode; $crate::trie_len(NEEDLES)] = $crate::build_trie(NEEDLES);
+
+        $crate::StaticNeedleSet::from_trie(NEEDLES, &TRIE)
+    }};
+}
+
+/// Something that can find the leftmost match of a pattern starting from a position in a
+/// haystack, which is all [`FindSearcher`] needs to implement `Searcher`
+trait Find {
+    fn find_from(&self, haystack: &str, from: usize) -> Option<(usize, usize)>;
+}
+
+impl Find for &StaticNeedleSet {
+    fn find_from(&self, haystack: &str, from: usize) -> Option<(usize, usize)> {
+        StaticNeedleSet::find_from(self, haystack, from)
+    }
+}
+
+/// A `Searcher` built on repeatedly calling `Find::find_from`
+#[derive(Clone, Debug)]
+struct FindSearcher<'a, F> {
+    haystack: &'a str,
+    finder: F,
+    position: usize,
+    next_match: Option<(usize, usize)>,
+}
+
+impl<'a, F> FindSearcher<'a, F> {
+    fn new(haystack: &'a str, finder: F) -> Self {
+        FindSearcher {
+            haystack,
+            finder,
+            position: 0,
+            next_match: None,
+        }
+    }
+}
+
+unsafe impl<'a, F> Searcher<'a> for FindSearcher<'a, F>
+where
+    F: Find,
+{
+    fn haystack(&self) -> &'a str {
+        self.haystack
+    }
+
+    fn next(&mut self) -> SearchStep {
+        if self.position == self.haystack.len() {
+            return SearchStep::Done;
+        }
+
+        let found = match self.next_match {
+            Some(found) => Some(found),
+            None => self.finder.find_from(self.haystack, self.position),
+        };
+
+        let start = self.position;
+
+        match found {
+            Some((match_start, match_end)) if match_start == start => {
+                self.next_match = None;
+                self.position = match_end;
+
+                SearchStep::Match(match_start, match_end)
+            }
+            Some((match_start, _)) => {
+                self.next_match = found;
+                self.position = match_start;
+
+                SearchStep::Reject(start, match_start)
+            }
+            None => {
+                self.position = self.haystack.len();
+
+                SearchStep::Reject(start, self.position)
+            }
+        }
+    }
+}
+
+/// Associated type for `<&StaticNeedleSet as Pattern<'a>>::Searcher`.
+#[derive(Clone, Debug)]
+pub struct NeedleSetSearcher<'a, 'b>(FindSearcher<'a, &'b StaticNeedleSet>);
+
+impl<'a, 'b> Pattern<'a> for &'b StaticNeedleSet {
+    type Searcher = NeedleSetSearcher<'a, 'b>;
+
+    fn into_searcher(self, haystack: &'a str) -> Self::Searcher {
+        NeedleSetSearcher(FindSearcher::new(haystack, self))
+    }
+}
+
+unsafe impl<'a, 'b> Searcher<'a> for NeedleSetSearcher<'a, 'b> {
+    fn haystack(&self) -> &'a str {
+        self.0.haystack()
+    }
+
+    fn next(&mut self) -> SearchStep {
+        self.0.next()
+    }
+}
+
+/// A pattern matching a needle even where it is split across lines by a hyphen, as is common in
+/// text extracted from PDFs: `Dehyphenated("example")` matches both `"example"` and
+/// `"exam-\nple"`.
+///
+/// A soft break is a `-` directly followed by `\n` or `\r\n`. Soft breaks are only skipped between
+/// two characters of the needle, so a match never starts or ends with one, and they are part of the
+/// match, so replacing a match removes the soft breaks inside it along with the rest. Soft breaks
+/// outside of matches are left alone. An empty needle never matches.
+#[derive(Clone, Copy, Debug)]
+pub struct Dehyphenated<'n>(pub &'n str);
+
+impl<'n> Dehyphenated<'n> {
+    /// The end of the match starting at `haystack[start..]`, if there is one.
+    ///
+    /// The states `(position, k)`, with `needle[k..]` left to match at `haystack[position..]`, are
+    /// searched depth-first, skipping a soft break before trying to match it. They are kept in
+    /// `stack` rather than on the call stack, because a needle can be longer than the call stack
+    /// could go deep.
+    ///
+    /// A needle that contains soft breaks itself lets each soft break in the haystack be either
+    /// skipped or matched, so the same state can be reached along many paths. Each one is only
+    /// tried once, and recorded in `tried`: the position only ever moves forward, so by the time a
+    /// state is reached again, everything after it has been tried without finding a match.
+    fn match_at(
+        &self,
+        haystack: &[u8],
+        start: usize,
+        stack: &mut Vec<(usize, usize)>,
+        tried: &mut Tried,
+    ) -> Option<usize> {
+        let needle = self.0.as_bytes();
+
+        stack.clear();
+        stack.push((start, 0));
+
+        while let Some((position, k)) = stack.pop() {
+            if k == needle.len() {
+                return Some(position);
+            }
+
+            // the start of a match is only ever reached once
+            if k > 0 && !tried.insert(position, k) {
+                continue;
+            }
+
+            // pushed in reverse, so that skipping a soft break is tried first
+            if haystack.get(position) == Some(&needle[k]) {
+                stack.push((position + 1, k + 1));
+            }
+
+            if k > 0 {
+                for soft_break in [&b"-\r\n"[..], &b"-\n"[..]] {
+                    if haystack[position..].starts_with(soft_break) {
+                        stack.push((position + soft_break.len(), k));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The `(position, k)` states [`Dehyphenated::match_at`] already tried from the current match
+/// start. Any number of soft breaks can be skipped in a row, so matches have no upper bound on
+/// their length, and the states are kept in a set rather than a table.
+#[derive(Default)]
+struct Tried(HashSet<(usize, usize)>);
+
+impl Tried {
+    /// Start over for the next match start
+    fn reset(&mut self) {
+        // clearing goes through the whole table, and most starts don't get far enough to need it
+        if !self.0.is_empty() {
+            self.0.clear();
+        }
+    }
+
+    /// Record a state, returning whether it is new
+    fn insert(&mut self, position: usize, k: usize) -> bool {
+        self.0.insert((position, k))
+    }
+}
+
+impl Find for Dehyphenated<'_> {
+    fn find_from(&self, haystack: &str, from: usize) -> Option<(usize, usize)> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let mut stack = Vec::new();
+        let mut tried = Tried::default();
+
+        // the needle starts with a whole character, so it can only match at char boundaries
+        (from..haystack.len()).find_map(|start| {
+            tried.reset();
+            self.match_at(haystack.as_bytes(), start, &mut stack, &mut tried)
+                .map(|end| (start, end))
+        })
+    }
+}
+
+/// Associated type for `<Dehyphenated as Pattern<'a>>::Searcher`.
+#[derive(Clone, Debug)]
+pub struct DehyphenatedSearcher<'a, 'n>(FindSearcher<'a, Dehyphenated<'n>>);
+
+impl<'a, 'n> Pattern<'a> for Dehyphenated<'n> {
+    type Searcher = DehyphenatedSearcher<'a, 'n>;
+
+    fn into_searcher(self, haystack: &'a str) -> Self::Searcher {
+        DehyphenatedSearcher(FindSearcher::new(haystack, self))
+    }
+}
+
+unsafe impl<'a, 'n> Searcher<'a> for DehyphenatedSearcher<'a, 'n> {
+    fn haystack(&self) -> &'a str {
+        self.0.haystack()
+    }
+
+    fn next(&mut self) -> SearchStep {
+        self.0.next()
+    }
+}
+
+/// The most needles a [`StaticNeedles`] searches for directly, without building a
+/// [`StaticNeedleSet`], which is as many first bytes as `memchr3` can look for at once
+const FEW_NEEDLES: usize = 3;
+
+/// A pattern matching any of a `'static` slice of needles, which needs no setup by the caller.
+///
+/// Up to three needles are searched for directly, by looking for their first bytes with `memchr3`.
+/// For more needles than that, a [`StaticNeedleSet`] is built the first time the slice is used, and
+/// cached for the rest of the program, keyed by the address and length of the slice: using the same
+/// slice again reuses it, but an equal slice elsewhere in memory may get a set of its own. Cached
+/// sets are never freed, which is fine for slices that are `static` or `const` items, but means
+/// building slices at runtime (by leaking them, for example) grows the cache without bound.
+///
+/// The set is looked up once, in [`new`](StaticNeedles::new), and not again when searching. Once a
+/// slice has been used, looking it up again only takes a shared lock, so threads looking up sets
+/// that are already built don't wait for each other.
+///
+/// Where several needles match at the same position, the longest one wins. Empty needles never
+/// match.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticNeedles(Needles);
+
+#[derive(Clone, Copy, Debug)]
+enum Needles {
+    Few(&'static [&'static str]),
+    Many(&'static StaticNeedleSet),
+}
+
+impl StaticNeedles {
+    /// Create a new instance of this type
+    pub fn new(needles: &'static [&'static str]) -> Self {
+        if needles.len() <= FEW_NEEDLES {
+            StaticNeedles(Needles::Few(needles))
+        } else {
+            StaticNeedles(Needles::Many(cached_set(needles)))
+        }
+    }
+}
+
+/// The set built for `needles`, building and caching it first if this is the first time they are
+/// used
+fn cached_set(needles: &'static [&'static str]) -> &'static StaticNeedleSet {
+    type Cache = RwLock<HashMap<(usize, usize), &'static StaticNeedleSet>>;
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+
+    let cache = CACHE.get_or_init(Default::default);
+    let key = (needles.as_ptr() as usize, needles.len());
+
+    // the cache is only ever inserted into, so it is still consistent after a panic
+    if let Some(&set) = cache
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+    {
+        return set;
+    }
+
+    // another thread may have built the set in the meantime, in which case its set is used
+    cache
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(key)
+        .or_insert_with(|| Box::leak(Box::new(StaticNeedleSet::new(needles))))
+}
+
+impl Find for StaticNeedles {
+    fn find_from(&self, haystack: &str, from: usize) -> Option<(usize, usize)> {
+        match self.0 {
+            Needles::Few(needles) => find_few(haystack, from, needles),
+            Needles::Many(set) => set.find_from(haystack, from),
+        }
+    }
+}
+
+/// Find the leftmost-longest match at or after `from` of up to three `needles`, returning its range
+fn find_few(haystack: &str, mut from: usize, needles: &[&str]) -> Option<(usize, usize)> {
+    let mut first_bytes = needles.iter().filter_map(|needle| needle.bytes().next());
+    let a = first_bytes.next()?;
+    let b = first_bytes.next().unwrap_or(a);
+    let c = first_bytes.next().unwrap_or(b);
+    let bytes = haystack.as_bytes();
+
+    while let Some(i) = memchr::memchr3(a, b, c, &bytes[from..]) {
+        let start = from + i;
+        let longest = needles
+            .iter()
+            .filter(|needle| !needle.is_empty() && bytes[start..].starts_with(needle.as_bytes()))
+            .map(|needle| needle.len())
+            .max();
+
+        if let Some(len) = longest {
+            return Some((start, start + len));
+        }
+
+        from = start + 1;
+    }
+
+    None
+}
+
+/// Associated type for `<StaticNeedles as Pattern<'a>>::Searcher`.
+#[derive(Clone, Debug)]
+pub struct StaticNeedlesSearcher<'a>(FindSearcher<'a, StaticNeedles>);
+
+impl<'a> Pattern<'a> for StaticNeedles {
+    type Searcher = StaticNeedlesSearcher<'a>;
+
+    fn into_searcher(self, haystack: &'a str) -> Self::Searcher {
+        StaticNeedlesSearcher(FindSearcher::new(haystack, self))
+    }
+}
+
+unsafe impl<'a> Searcher<'a> for StaticNeedlesSearcher<'a> {
+    fn haystack(&self) -> &'a str {
+        self.0.haystack()
+    }
+
+    fn next(&mut self) -> SearchStep {
+        self.0.next()
+    }
+}
+
+/// Find the leftmost position at or after `from` where one of `needles` matches, returning that
+/// position and the index of the longest needle matching there. Empty needles never match.
+pub(crate) fn find_leftmost_longest<S>(
+    haystack: &str,
+    from: usize,
+    needles: &[S],
+) -> Option<(usize, usize)>
+where
+    S: AsRef<str>,
+{
+    find_leftmost_longest_by(haystack, from, needles, AsRef::as_ref)
+}
+
+/// Like [`find_leftmost_longest`], but for items that each contain a needle, which `needle`
+/// extracts
+pub(crate) fn find_leftmost_longest_by<T, F>(
+    haystack: &str,
+    from: usize,
+    items: &[T],
+    needle: F,
+) -> Option<(usize, usize)>
+where
+    F: Fn(&T) -> &str,
+{
+    let bytes = haystack.as_bytes();
+
+    (from..haystack.len()).find_map(|start| {
+        // comparing bytes means the haystack is never sliced inside a character, and a needle
+        // can't match there anyway since it starts with a whole character
+        items
+            .iter()
+            .map(&needle)
+            .enumerate()
+            .filter(|(_, needle)| {
+                !needle.is_empty() && bytes[start..].starts_with(needle.as_bytes())
+            })
+            .max_by(|(i, a), (j, b)| a.len().cmp(&b.len()).then(j.cmp(i)))
+            .map(|(i, _)| (start, i))
+    })
+}
+
+/// The escape sequence written after each match by [`Highlighted`], which resets the terminal to
+/// its default style
+pub const ANSI_RESET: &str = "\x1b[0m";
+
+/// A lazily-highlighted string where each of several needles is wrapped in its own ANSI style, so
+/// different needles can be shown in different colors. Each `(needle, style)` pair's matches are
+/// preceded by its style and followed by [`ANSI_RESET`].
+///
+/// All needles are searched for in a single pass. Where several of them match at the same
+/// position, the longest one wins, or the first of those if they are equally long. Empty needles
+/// never match.
+pub struct Highlighted<'a, 'p> {
+    haystack: &'a str,
+    pairs: &'p [(&'p str, &'p str)],
+}
+
+impl<'a, 'p> Highlighted<'a, 'p> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, pairs: &'p [(&'p str, &'p str)]) -> Self {
+        Highlighted { haystack, pairs }
+    }
+}
+
+impl<'a, 'p> fmt::Display for Highlighted<'a, 'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut position = 0;
+
+        while let Some((start, i)) =
+            find_leftmost_longest_by(self.haystack, position, self.pairs, |&(needle, _)| needle)
+        {
+            let (needle, style) = self.pairs[i];
+            let end = start + needle.len();
+
+            f.write_str(&self.haystack[position..start])?;
+            write!(f, "{}{}{}", style, &self.haystack[start..end], ANSI_RESET)?;
+            position = end;
+        }
+
+        f.write_str(&self.haystack[position..])
+    }
+}
+
+/// A lazily-replaced string where each of several needles is replaced with its own replacement,
+/// with the `(needle, replacement)` pairs coming from any iterator.
+///
+/// The pairs are collected when this is created, so the iterator is only gone through once, and
+/// all needles are then searched for in a single pass on every render. Where several of them match
+/// at the same position, the longest one wins, or the first of those if they are equally long.
+/// Empty needles never match.
+pub struct ReplacedPairs<'a, 'n, R> {
+    haystack: &'a str,
+    pairs: Vec<(&'n str, R)>,
+}
+
+impl<'a, 'n, R> ReplacedPairs<'a, 'n, R> {
+    /// Create a new instance of this type
+    pub fn new<I>(haystack: &'a str, pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (&'n str, R)>,
+    {
+        ReplacedPairs {
+            haystack,
+            pairs: pairs.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a, 'n, R> fmt::Display for ReplacedPairs<'a, 'n, R>
+where
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut position = 0;
+
+        while let Some((start, i)) =
+            find_leftmost_longest_by(self.haystack, position, &self.pairs, |&(needle, _)| needle)
+        {
+            let (needle, replacement) = &self.pairs[i];
+
+            f.write_str(&self.haystack[position..start])?;
+            write_replacement(f, replacement)?;
+            position = start + needle.len();
+        }
+
+        f.write_str(&self.haystack[position..])
+    }
+}
+
+/// A lazily-replaced string where two needles are swapped for each other in a single pass, so
+/// replacing `a` with `b` can't cause those `b`s to be replaced with `a` again.
+///
+/// Where both needles match at the same position, the longer one is replaced.
+pub struct Swapped<'a, 'n> {
+    haystack: &'a str,
+    needles: [&'n str; 2],
+}
+
+impl<'a, 'n> Swapped<'a, 'n> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, a: &'n str, b: &'n str) -> Self {
+        Swapped {
+            haystack,
+            needles: [a, b],
+        }
+    }
+}
+
+impl<'a, 'n> fmt::Display for Swapped<'a, 'n> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut position = 0;
+
+        while let Some((start, i)) = find_leftmost_longest(self.haystack, position, &self.needles) {
+            f.write_str(&self.haystack[position..start])?;
+            write_replacement(f, self.needles[1 - i])?;
+            position = start + self.needles[i].len();
+        }
+
+        f.write_str(&self.haystack[position..])
+    }
+}
+
+/// A lazily-replaced string where each ASCII character is looked up by its code in a table of
+/// replacements. Characters with a replacement of `None`, as well as all non-ASCII characters, are
+/// left as they are.
+pub struct ReplacedTable<'a, 't, R> {
+    haystack: &'a str,
+    table: &'t [Option<R>; 128],
+}
+
+impl<'a, 't, R> ReplacedTable<'a, 't, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, table: &'t [Option<R>; 128]) -> Self {
+        ReplacedTable { haystack, table }
+    }
+}
+
+impl<'a, 't, R> fmt::Display for ReplacedTable<'a, 't, R>
+where
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut last = 0;
+
+        // ASCII bytes never occur inside multi-byte characters, so every index found here is a
+        // character boundary
+        for (i, &byte) in self.haystack.as_bytes().iter().enumerate() {
+            if let Some(Some(replacement)) = self.table.get(byte as usize) {
+                f.write_str(&self.haystack[last..i])?;
+                write_replacement(f, replacement)?;
+                last = i + 1;
+            }
+        }
+
+        f.write_str(&self.haystack[last..])
+    }
+}
+
+/// A lazily-replaced string where keys from a table sorted by key are replaced with their values,
+/// which needs less memory than a `HashMap` or a trie for large, static dictionaries.
+///
+/// At each position, every prefix of the rest of the text that is no longer than the longest key
+/// is looked up with a binary search, starting with the longest, so where several keys match at
+/// the same position, the longest one wins. This costs up to one binary search per byte of the
+/// longest key at each position. The table must be sorted by key, as by `slice::sort`, or the
+/// searches may miss keys that are in it. Empty keys never match.
+pub struct ReplacedSorted<'a, 't> {
+    haystack: &'a str,
+    table: &'t [(&'t str, &'t str)],
+    max_key_len: usize,
+}
+
+impl<'a, 't> ReplacedSorted<'a, 't> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, table: &'t [(&'t str, &'t str)]) -> Self {
+        let max_key_len = table.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+
+        ReplacedSorted {
+            haystack,
+            table,
+            max_key_len,
+        }
+    }
+}
+
+impl<'a, 't> fmt::Display for ReplacedSorted<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision();
+        write_longest_keys(f, precision, self.haystack, self.max_key_len, |key| {
+            self.table
+                .binary_search_by(|&(candidate, _)| candidate.cmp(key))
+                .ok()
+                .map(|i| self.table[i].1)
+        })
+    }
+}
+
+/// Write `haystack`, replacing the longest key at each position with its value, as looked up by
+/// `lookup`. At each position, every prefix of the rest of the text up to `max_key_len` bytes long
+/// that ends at a character boundary is looked up, starting with the longest.
+pub(crate) fn write_longest_keys<'v, W, F>(
+    w: &mut W,
+    precision: Option<usize>,
+    haystack: &str,
+    max_key_len: usize,
+    lookup: F,
+) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+    F: Fn(&str) -> Option<&'v str>,
+{
+    let mut last = 0;
+    let mut position = 0;
+
+    while let Some(c) = haystack[position..].chars().next() {
+        let rest = &haystack[position..];
+        let found = (1..=max_key_len.min(rest.len()))
+            .rev()
+            .filter(|&len| rest.is_char_boundary(len))
+            .find_map(|len| lookup(&rest[..len]).map(|value| (len, value)));
+
+        match found {
+            Some((len, value)) => {
+                w.write_str(&haystack[last..position])?;
+                write_with_precision(w, precision, value)?;
+                position += len;
+                last = position;
+            }
+            None => position += c.len_utf8(),
+        }
+    }
+
+    w.write_str(&haystack[last..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_set, trie_len, StaticNeedleSet};
+    use crate::LazyReplace;
+    use std::ptr;
+
+    static SET: StaticNeedleSet = needle_set!["foo", "bar", "foobar", ""];
+
+    #[test]
+    fn replace_set() {
+        assert_eq!(
+            "[] and [], [] and []",
+            "foo and bar, foo and foobar"
+                .lazy_replace_set(&SET, "[]")
+                .to_string()
+        );
+        assert_eq!(
+            "nothing to see",
+            "nothing to see".lazy_replace_set(&SET, "[]").to_string()
+        );
+        assert_eq!("", "".lazy_replace_set(&SET, "[]").to_string());
+        assert_eq!("ö[]ö", "öbarö".lazy_replace_set(&SET, "[]").to_string());
+    }
+
+    #[test]
+    fn trie_shares_prefixes() {
+        static SHARED: StaticNeedleSet = needle_set!["ab", "abc", "abd", "b", "€", "€uro"];
+
+        assert_eq!(12, trie_len(SHARED.needles()));
+        assert_eq!(
+            "____x__",
+            "abcabdabbx€uro€".lazy_replace_set(&SHARED, "_").to_string()
+        );
+        assert_eq!(
+            "a_x",
+            "aabdx"
+                .lazy_replace_set(&StaticNeedleSet::new(SHARED.needles()), "_")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn const_and_runtime_sets_agree() {
+        let runtime = StaticNeedleSet::new(SET.needles());
+
+        assert_eq!(
+            "foobarbaz".lazy_replace_set(&SET, "-").to_string(),
+            "foobarbaz".lazy_replace_set(&runtime, "-").to_string()
+        );
+    }
+
+    #[test]
+    fn replace_static() {
+        static FEW: &[&str] = &["foo", "foobar", ""];
+        static MANY: &[&str] = &["one", "three", "four", "five", "fifteen"];
+
+        assert_eq!(
+            "[] and [] or bar",
+            "foo and foobar or bar"
+                .lazy_replace_static(FEW, "[]")
+                .to_string()
+        );
+        assert_eq!(
+            "#, #, #, # and six",
+            "one, three, five, fifteen and six"
+                .lazy_replace_static(MANY, "#")
+                .to_string()
+        );
+        assert_eq!("ö#ö", "öfourö".lazy_replace_static(MANY, "#").to_string());
+    }
+
+    #[test]
+    fn static_sets_are_cached() {
+        static MANY: &[&str] = &["a", "b", "c", "d", "e"];
+
+        assert!(ptr::eq(cached_set(MANY), cached_set(MANY)));
+        assert!(ptr::eq(cached_set(&MANY[1..]), cached_set(&MANY[1..])));
+        assert!(!ptr::eq(cached_set(MANY), cached_set(&MANY[..4])));
+    }
+
+    #[test]
+    fn highlight_many() {
+        const RED: &str = "\x1b[31m";
+        const GREEN: &str = "\x1b[32m";
+        const BOLD: &str = "\x1b[1m";
+
+        let pairs = [("error", RED), ("ok", GREEN), ("errors", BOLD), ("", BOLD)];
+
+        assert_eq!(
+            "\x1b[32mok\x1b[0m, \x1b[31merror\x1b[0m, \x1b[1merrors\x1b[0m",
+            "ok, error, errors".lazy_highlight_many(&pairs).to_string()
+        );
+        assert_eq!("plain", "plain".lazy_highlight_many(&pairs).to_string());
+        assert_eq!(
+            "ö\x1b[32mok\x1b[0mö",
+            "öokö".lazy_highlight_many(&pairs).to_string()
+        );
+        assert_eq!(
+            "\x1b[31mok\x1b[0m",
+            "ok".lazy_highlight_many(&[("ok", RED), ("ok", GREEN)])
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_iter_needles() {
+        let source = "name=Ada\nnames=Ada, Grace\nlang=Rust";
+        let vars = source.lines().filter_map(|line| line.split_once('='));
+
+        assert_eq!(
+            "Ada and Ada, Grace use Rust",
+            "name and names use lang"
+                .lazy_replace_iter_needles(vars)
+                .to_string()
+        );
+
+        let words = ["one", "two", "three"];
+        assert_eq!(
+            "1, 2, 3",
+            "one, two, three"
+                .lazy_replace_iter_needles(words.iter().copied().zip(1..))
+                .to_string()
+        );
+
+        assert_eq!(
+            "1b",
+            "ab".lazy_replace_iter_needles(vec![("", 0), ("a", 1), ("a", 2)])
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_sorted() {
+        let table = [
+            ("colour", "color"),
+            ("colours", "colors"),
+            ("grey", "gray"),
+            ("metre", "meter"),
+            ("ö", "oe"),
+        ];
+        assert!(table.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        assert_eq!(
+            "grayish colors, a 2 meter color swatch",
+            "greyish colours, a 2 metre colour swatch"
+                .lazy_replace_sorted(&table)
+                .to_string()
+        );
+        assert_eq!("Groesse", "Grösse".lazy_replace_sorted(&table).to_string());
+        assert_eq!(
+            "nothing to change",
+            "nothing to change".lazy_replace_sorted(&table).to_string()
+        );
+        assert_eq!("colo", "colo".lazy_replace_sorted(&table).to_string());
+        assert_eq!("abc", "abc".lazy_replace_sorted(&[]).to_string());
+    }
+
+    #[test]
+    fn replace_table() {
+        let mut table: [Option<&str>; 128] = [None; 128];
+        table[b'<' as usize] = Some("&lt;");
+        table[b'>' as usize] = Some("&gt;");
+        table[b'&' as usize] = Some("&amp;");
+
+        assert_eq!(
+            "&lt;a&gt; &amp; ö &lt;/a&gt;",
+            "<a> & ö </a>".lazy_replace_table(&table).to_string()
+        );
+        assert_eq!("plain", "plain".lazy_replace_table(&table).to_string());
+    }
+
+    #[test]
+    fn swap() {
+        assert_eq!(
+            "the dog chased the cat, then the cat chased the dog",
+            "the cat chased the dog, then the dog chased the cat"
+                .lazy_swap("cat", "dog")
+                .to_string()
+        );
+        assert_eq!("ba ab", "ab ba".lazy_swap("ab", "ba").to_string());
+        assert_eq!("xyzx", "xxyz".lazy_swap("x", "xyz").to_string());
+        assert_eq!("", "".lazy_swap("a", "b").to_string());
+    }
+
+    #[test]
+    fn replace_dehyphenated() {
+        assert_eq!(
+            "an sample of an sample",
+            "an exam-\nple of an example"
+                .lazy_replace_dehyphenated("example", "sample")
+                .to_string()
+        );
+        assert_eq!(
+            "e-\nmail, email, @, @",
+            "e-\nmail, email, e-mail, e-\r\n-mail"
+                .lazy_replace_dehyphenated("e-mail", "@")
+                .to_string()
+        );
+        assert_eq!(
+            "-\n_-\n _",
+            "-\nword-\n wo-\r\nrd"
+                .lazy_replace_dehyphenated("word", "_")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn dehyphenated_needle_with_soft_breaks() {
+        let haystack = format!("a{}b", "-\n".repeat(10));
+        assert_eq!(
+            "_",
+            haystack.lazy_replace_dehyphenated("ab", "_").to_string()
+        );
+
+        assert_eq!(
+            "_, _, a-b",
+            "a-\nb, a-\n-\nb, a-b"
+                .lazy_replace_dehyphenated("a-\nb", "_")
+                .to_string()
+        );
+
+        // each soft break could be skipped or matched, which used to be tried in every
+        // combination before giving up
+        let needle = format!("x{}y", "-\n".repeat(40));
+        let haystack = format!("x{}z", "-\n".repeat(44));
+        assert_eq!(
+            haystack,
+            haystack.lazy_replace_dehyphenated(&needle, "_").to_string()
+        );
+
+        let haystack = format!("x{}y", "-\n".repeat(44));
+        assert_eq!(
+            "_",
+            haystack.lazy_replace_dehyphenated(&needle, "_").to_string()
+        );
+    }
+
+    #[test]
+    fn long_dehyphenated_needle() {
+        // one state per byte of the needle, which used to be one stack frame each
+        let needle = "a".repeat(200_000);
+        assert_eq!(
+            "X",
+            needle.lazy_replace_dehyphenated(&needle, "X").to_string()
+        );
+
+        let haystack = format!("{}-\r\n{}, a", &needle[..50_000], &needle[50_000..]);
+        assert_eq!(
+            "X, a",
+            haystack.lazy_replace_dehyphenated(&needle, "X").to_string()
+        );
+    }
+}
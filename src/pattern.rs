@@ -3,6 +3,18 @@
 //! For more details, see the traits [`Pattern`], [`Searcher`],
 //! [`ReverseSearcher`], and [`DoubleEndedSearcher`].
 
+// Kept as close to the `core` source as possible, so lints that would need it to diverge are allowed.
+#![allow(
+    clippy::legacy_numeric_constants,
+    clippy::manual_contains,
+    clippy::match_like_matches_macro,
+    clippy::missing_safety_doc,
+    clippy::needless_lifetimes,
+    clippy::op_ref,
+    clippy::question_mark,
+    clippy::wrong_self_convention
+)]
+
 use memchr;
 use std::{cmp, fmt, str::CharIndices, usize};
 
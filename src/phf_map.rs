@@ -0,0 +1,77 @@
+//! Replacements looked up in a perfect-hash map generated at compile time. Requires the `phf`
+//! feature, which pulls in the `phf` crate.
+
+use crate::needles::write_longest_keys;
+use phf::Map;
+use std::fmt;
+
+/// A lazily-replaced string where keys of a [`phf::Map`], which can be generated at compile time
+/// with `phf_map!` or a build script, are replaced with their values.
+///
+/// A perfect-hash map can only look up whole keys, so at each position every prefix of the rest of
+/// the text that is no longer than the longest key is looked up, starting with the longest, and the
+/// first one found is replaced. When all keys are single characters, this means that each character
+/// is looked up exactly once; longer keys cost up to one lookup per byte of the longest key at each
+/// position. The longest key is found when this is created, which goes through all keys once.
+/// Empty keys never match.
+pub struct ReplacedPhf<'a, 'm> {
+    haystack: &'a str,
+    map: &'m Map<&'static str, &'static str>,
+    max_key_len: usize,
+}
+
+impl<'a, 'm> ReplacedPhf<'a, 'm> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, map: &'m Map<&'static str, &'static str>) -> Self {
+        let max_key_len = map.keys().map(|key| key.len()).max().unwrap_or(0);
+
+        ReplacedPhf {
+            haystack,
+            map,
+            max_key_len,
+        }
+    }
+}
+
+impl<'a, 'm> fmt::Display for ReplacedPhf<'a, 'm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision();
+        write_longest_keys(f, precision, self.haystack, self.max_key_len, |key| {
+            self.map.get(key).copied()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LazyReplace;
+    use phf::{phf_map, Map};
+
+    static ENTITIES: Map<&'static str, &'static str> = phf_map! {
+        "<" => "&lt;",
+        ">" => "&gt;",
+        "&" => "&amp;",
+    };
+
+    static WORDS: Map<&'static str, &'static str> = phf_map! {
+        "colour" => "color",
+        "colours" => "colors",
+        "ö" => "oe",
+    };
+
+    #[test]
+    fn replace_phf() {
+        assert_eq!(
+            "&lt;a&gt; &amp; ö &lt;/a&gt;",
+            "<a> & ö </a>".lazy_replace_phf(&ENTITIES).to_string()
+        );
+        assert_eq!(
+            "colors and a color, but colo",
+            "colours and a colour, but colo"
+                .lazy_replace_phf(&WORDS)
+                .to_string()
+        );
+        assert_eq!("Groesse", "Grösse".lazy_replace_phf(&WORDS).to_string());
+        assert_eq!("plain", "plain".lazy_replace_phf(&WORDS).to_string());
+    }
+}
@@ -0,0 +1,141 @@
+//! Replacements at positions given up front, rather than found by searching for a needle.
+
+use crate::{write_replacement, ReplaceBuildError};
+use std::{fmt, ops::Range};
+
+/// A lazily-replaced string where byte ranges given up front are replaced, such as ones produced
+/// by a parser or another tool.
+///
+/// The ranges should be sorted by their start and shouldn't overlap. This isn't checked: a range
+/// that starts before the end of the last range that was replaced is skipped, so where two ranges
+/// overlap, the first one wins. Use [`try_lazy_replace_at_ranges`] to get an error instead.
+/// Adjacent ranges like `0..2` and `2..4` don't overlap, and an empty range inserts the
+/// replacement without removing anything.
+///
+/// Rendering panics if a range is out of bounds or doesn't start and end at character boundaries.
+///
+/// [`try_lazy_replace_at_ranges`]: crate::LazyReplace::try_lazy_replace_at_ranges
+pub struct ReplacedRanges<'a, 'r, R> {
+    haystack: &'a str,
+    ranges: &'r [Range<usize>],
+    replacement: R,
+}
+
+impl<'a, 'r, R> ReplacedRanges<'a, 'r, R> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, ranges: &'r [Range<usize>], replacement: R) -> Self {
+        ReplacedRanges {
+            haystack,
+            ranges,
+            replacement,
+        }
+    }
+
+    /// Like `new`, but returns an error instead if the ranges overlap or aren't sorted by their
+    /// start
+    pub fn try_new(
+        haystack: &'a str,
+        ranges: &'r [Range<usize>],
+        replacement: R,
+    ) -> Result<Self, ReplaceBuildError> {
+        for (a, pair) in ranges.windows(2).enumerate() {
+            let (earlier, later) = (&pair[0], &pair[1]);
+            let b = a + 1;
+
+            if later.start < earlier.start {
+                return Err(ReplaceBuildError::UnsortedRanges { a, b });
+            }
+            if later.start < earlier.end {
+                return Err(ReplaceBuildError::Overlap { a, b });
+            }
+        }
+
+        Ok(ReplacedRanges::new(haystack, ranges, replacement))
+    }
+}
+
+impl<'a, 'r, R> fmt::Display for ReplacedRanges<'a, 'r, R>
+where
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut position = 0;
+
+        for range in self.ranges {
+            if range.start < position {
+                continue;
+            }
+
+            f.write_str(&self.haystack[position..range.start])?;
+            write_replacement(f, &self.replacement)?;
+            position = range.end;
+        }
+
+        f.write_str(&self.haystack[position..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LazyReplace, ReplaceBuildError};
+
+    #[test]
+    fn replace_at_ranges() {
+        assert_eq!(
+            "_ is _",
+            "this is fine"
+                .lazy_replace_at_ranges(&[0..4, 8..12], '_')
+                .to_string()
+        );
+        assert_eq!(
+            "__cd",
+            "abcd"
+                .lazy_replace_at_ranges(&[0..1, 1..2], '_')
+                .to_string()
+        );
+        assert_eq!(
+            "a_b_",
+            "ab".lazy_replace_at_ranges(&[1..1, 2..2], '_').to_string()
+        );
+        // the second range overlaps the first, so it is skipped
+        assert_eq!(
+            "_d",
+            "abcd"
+                .lazy_replace_at_ranges(&[0..3, 1..4], '_')
+                .to_string()
+        );
+        assert_eq!(
+            "unchanged",
+            "unchanged".lazy_replace_at_ranges(&[], '_').to_string()
+        );
+    }
+
+    #[test]
+    fn try_replace_at_ranges() {
+        assert_eq!(
+            "__cd",
+            "abcd"
+                .try_lazy_replace_at_ranges(&[0..1, 1..2], '_')
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            Some(ReplaceBuildError::Overlap { a: 1, b: 2 }),
+            "abcd"
+                .try_lazy_replace_at_ranges(&[0..1, 1..3, 2..4], '_')
+                .err()
+        );
+        assert_eq!(
+            Some(ReplaceBuildError::Overlap { a: 0, b: 1 }),
+            "abcd".try_lazy_replace_at_ranges(&[0..3, 1..1], '_').err()
+        );
+        assert_eq!(
+            Some(ReplaceBuildError::UnsortedRanges { a: 0, b: 1 }),
+            "abcd".try_lazy_replace_at_ranges(&[2..3, 0..1], '_').err()
+        );
+        assert_eq!(
+            Some(ReplaceBuildError::UnsortedRanges { a: 0, b: 1 }),
+            "abcd".try_lazy_replace_at_ranges(&[2..2, 0..2], '_').err()
+        );
+    }
+}
@@ -0,0 +1,758 @@
+//! Helpers that render replacements eagerly, returning the output along with anything else they found out.
+
+use crate::{
+    pattern::{Pattern, Searcher},
+    write_matches, LazyReplace, ReplacedRanges, VerifyError,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    hash::Hasher,
+    ops::Range,
+};
+
+/// See [`LazyReplace::render_diff`](crate::LazyReplace::render_diff)
+pub(crate) fn diff<'a, P, R>(haystack: &'a str, pat: P, replacement: R) -> String
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut out = String::with_capacity(haystack.len());
+
+    write_matches(&mut out, haystack, pat, |out, _, range| {
+        write!(out, "[-{}-]{{+{}+}}", &haystack[range], replacement)
+    })
+    .expect("a Display implementation returned an error unexpectedly");
+
+    out
+}
+
+/// The marker [`LazyReplace::render_annotated`](crate::LazyReplace::render_annotated) puts in front
+/// of each replacement, `U+2068 FIRST STRONG ISOLATE`
+pub const ANNOTATION_START: char = '\u{2068}';
+
+/// The marker [`LazyReplace::render_annotated`](crate::LazyReplace::render_annotated) puts behind
+/// each replacement, `U+2069 POP DIRECTIONAL ISOLATE`
+pub const ANNOTATION_END: char = '\u{2069}';
+
+/// See [`LazyReplace::render_annotated`](crate::LazyReplace::render_annotated)
+pub(crate) fn annotated<'a, P, R>(haystack: &'a str, pat: P, replacement: R) -> String
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut out = String::with_capacity(haystack.len());
+
+    write_matches(&mut out, haystack, pat, |out, _, _| {
+        write!(out, "{}{}{}", ANNOTATION_START, replacement, ANNOTATION_END)
+    })
+    .expect("a Display implementation returned an error unexpectedly");
+
+    out
+}
+
+/// The character that placeholder tokens of
+/// [`LazyReplace::render_tokenized`](crate::LazyReplace::render_tokenized) start and end with,
+/// `U+FFFC OBJECT REPLACEMENT CHARACTER`
+pub const TOKEN_DELIMITER: char = '\u{FFFC}';
+
+/// See [`LazyReplace::render_tokenized`](crate::LazyReplace::render_tokenized)
+pub(crate) fn tokenized<'a, P>(haystack: &'a str, pat: P) -> (String, Vec<String>)
+where
+    P: Pattern<'a>,
+{
+    let mut out = String::with_capacity(haystack.len());
+    let mut originals = Vec::new();
+
+    write_matches(&mut out, haystack, pat, |out, i, range| {
+        originals.push(haystack[range].to_string());
+        write!(out, "{}{}{}", TOKEN_DELIMITER, i, TOKEN_DELIMITER)
+    })
+    .expect("writing to a String can't fail");
+
+    (out, originals)
+}
+
+/// Undo [`LazyReplace::render_tokenized`](crate::LazyReplace::render_tokenized), replacing each
+/// placeholder token in `text` with the original text it stands for in `originals`.
+///
+/// The tokenized text may have been edited in between, as long as the tokens themselves are left
+/// alone. Anything that looks like a token but isn't one, because it isn't terminated, doesn't
+/// hold a decimal number or its number is out of range for `originals`, is left as it is. If the
+/// text contained [`TOKEN_DELIMITER`] before it was tokenized, it may be mistaken for a token.
+pub fn detokenize(text: &str, originals: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(TOKEN_DELIMITER) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + TOKEN_DELIMITER.len_utf8()..];
+
+        let original = rest.find(TOKEN_DELIMITER).and_then(|end| {
+            let digits = &rest[..end];
+            let original = digits
+                .bytes()
+                .all(|byte| byte.is_ascii_digit())
+                .then(|| digits.parse::<usize>().ok())
+                .flatten()
+                .and_then(|i| originals.get(i))?;
+
+            Some((end, original))
+        });
+
+        match original {
+            Some((end, original)) => {
+                out.push_str(original);
+                rest = &rest[end + TOKEN_DELIMITER.len_utf8()..];
+            }
+            // the delimiter may start a token later on, so only it is skipped
+            None => out.push(TOKEN_DELIMITER),
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// See [`LazyReplace::render_context`](crate::LazyReplace::render_context)
+pub(crate) fn context<'a, P, R>(
+    haystack: &'a str,
+    pat: P,
+    replacement: R,
+    before: usize,
+    after: usize,
+) -> Vec<String>
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut searcher = pat.into_searcher(haystack);
+    let mut snippets = Vec::new();
+
+    while let Some((start, end)) = searcher.next_match() {
+        let from = haystack[..start]
+            .char_indices()
+            .rev()
+            .take(before)
+            .last()
+            .map_or(start, |(i, _)| i);
+        let to = haystack[end..]
+            .char_indices()
+            .nth(after)
+            .map_or(haystack.len(), |(i, _)| end + i);
+
+        snippets.push(format!(
+            "{}{}{}",
+            &haystack[from..start],
+            replacement,
+            &haystack[end..to]
+        ));
+    }
+
+    snippets
+}
+
+/// See [`LazyReplace::render_both`](crate::LazyReplace::render_both)
+pub(crate) fn both<'a, P, A, B>(haystack: &'a str, pat: P, a: A, b: B) -> (String, String)
+where
+    P: Pattern<'a>,
+    A: fmt::Display,
+    B: fmt::Display,
+{
+    let mut searcher = pat.into_searcher(haystack);
+    let ranges: Vec<_> = std::iter::from_fn(|| searcher.next_match())
+        .map(|(start, end)| start..end)
+        .collect();
+
+    (
+        ReplacedRanges::new(haystack, &ranges, a).to_string(),
+        ReplacedRanges::new(haystack, &ranges, b).to_string(),
+    )
+}
+
+/// See [`LazyReplace::render_with_frequency`](crate::LazyReplace::render_with_frequency)
+pub(crate) fn with_frequency<'a, P, R>(
+    haystack: &'a str,
+    pat: P,
+    replacement: R,
+) -> (String, HashMap<String, usize>)
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut out = String::with_capacity(haystack.len());
+    let mut frequency = HashMap::new();
+
+    write_matches(&mut out, haystack, pat, |out, _, range| {
+        let matched = &haystack[range];
+        match frequency.get_mut(matched) {
+            Some(count) => *count += 1,
+            None => {
+                frequency.insert(matched.to_string(), 1);
+            }
+        }
+
+        write!(out, "{}", replacement)
+    })
+    .expect("a Display implementation returned an error unexpectedly");
+
+    (out, frequency)
+}
+
+/// One match found by [`LazyReplace::preview`](crate::LazyReplace::preview), along with what it
+/// would be replaced with
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ReplacePreview<'a> {
+    /// The byte range of the match in the haystack
+    pub range: Range<usize>,
+    /// The matched text
+    pub original: &'a str,
+    /// The rendered replacement
+    pub replacement: String,
+}
+
+/// See [`LazyReplace::preview`](crate::LazyReplace::preview)
+pub(crate) fn preview<'a, P, R>(
+    haystack: &'a str,
+    pat: P,
+    replacement: R,
+) -> Vec<ReplacePreview<'a>>
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut searcher = pat.into_searcher(haystack);
+
+    std::iter::from_fn(|| searcher.next_match())
+        .map(|(start, end)| ReplacePreview {
+            range: start..end,
+            original: &haystack[start..end],
+            replacement: replacement.to_string(),
+        })
+        .collect()
+}
+
+/// See [`LazyReplace::render_verified`](crate::LazyReplace::render_verified)
+pub(crate) fn verified<'a, P, R>(
+    haystack: &'a str,
+    pat: P,
+    replacement: R,
+    expected: usize,
+) -> Result<String, VerifyError>
+where
+    P: Pattern<'a>,
+    R: fmt::Display,
+{
+    let mut out = String::with_capacity(haystack.len());
+    let mut actual = 0;
+
+    write_matches(&mut out, haystack, pat, |out, _, _| {
+        actual += 1;
+        write!(out, "{}", replacement)
+    })
+    .expect("a Display implementation returned an error unexpectedly");
+
+    if actual == expected {
+        Ok(out)
+    } else {
+        Err(VerifyError { expected, actual })
+    }
+}
+
+/// See [`LazyReplace::render_try_with`](crate::LazyReplace::render_try_with)
+pub(crate) fn try_with<'a, P, F, D, E>(
+    haystack: &'a str,
+    pat: P,
+    mut f: F,
+) -> (String, Vec<(Range<usize>, E)>)
+where
+    P: Pattern<'a>,
+    F: FnMut(&str) -> Result<D, E>,
+    D: fmt::Display,
+{
+    let mut out = String::with_capacity(haystack.len());
+    let mut errors = Vec::new();
+
+    write_matches(&mut out, haystack, pat, |out, _, range| {
+        match f(&haystack[range.clone()]) {
+            Ok(replacement) => write!(out, "{}", replacement),
+            Err(error) => {
+                out.push_str(&haystack[range.clone()]);
+                errors.push((range, error));
+                Ok(())
+            }
+        }
+    })
+    .expect("a Display implementation returned an error unexpectedly");
+
+    (out, errors)
+}
+
+/// See [`LazyReplaceDisplay::render_aligned`](crate::LazyReplaceDisplay::render_aligned)
+pub(crate) fn aligned<D>(inner: &D, row_sep: char, col_sep: char) -> String
+where
+    D: fmt::Display + ?Sized,
+{
+    let table = inner.to_string();
+    let mut widths = Vec::new();
+
+    for row in table.split(row_sep) {
+        for (column, cell) in row.split(col_sep).enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(column) {
+                Some(max) => *max = width.max(*max),
+                None => widths.push(width),
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(table.len());
+
+    for (i, row) in table.split(row_sep).enumerate() {
+        if i > 0 {
+            out.push(row_sep);
+        }
+
+        let mut cells = row.split(col_sep).enumerate().peekable();
+        while let Some((column, cell)) = cells.next() {
+            out.push_str(cell);
+
+            if cells.peek().is_some() {
+                let padding = widths[column] - cell.chars().count();
+                out.extend(std::iter::repeat_n(' ', padding));
+                out.push(col_sep);
+            }
+        }
+    }
+
+    out
+}
+
+/// See [`LazyReplaceDisplay::collect_into`](crate::LazyReplaceDisplay::collect_into)
+pub(crate) fn collect_into<D, S>(inner: &D) -> S
+where
+    D: fmt::Display + ?Sized,
+    S: Extend<char> + Default,
+{
+    struct Collector<S>(S);
+
+    impl<S: Extend<char>> Write for Collector<S> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.extend(s.chars());
+            Ok(())
+        }
+    }
+
+    let mut collector = Collector(S::default());
+    write!(collector, "{}", inner)
+        .expect("a Display implementation returned an error unexpectedly");
+    collector.0
+}
+
+/// See [`LazyReplaceDisplay::digest`](crate::LazyReplaceDisplay::digest)
+pub(crate) fn digest<D, H>(inner: &D, hasher: &mut H) -> u64
+where
+    D: fmt::Display + ?Sized,
+    H: Hasher,
+{
+    struct Feeder<'h, H>(&'h mut H);
+
+    impl<H: Hasher> Write for Feeder<'_, H> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.write(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    write!(Feeder(&mut *hasher), "{}", inner)
+        .expect("a Display implementation returned an error unexpectedly");
+    hasher.finish()
+}
+
+/// See [`LazyReplaceDisplay::diff_against`](crate::LazyReplaceDisplay::diff_against)
+pub(crate) fn diff_against<D>(
+    inner: &D,
+    expected: &str,
+) -> Option<(usize, Option<char>, Option<char>)>
+where
+    D: fmt::Display + ?Sized,
+{
+    struct Comparer<'e> {
+        expected: &'e str,
+        offset: usize,
+        difference: Option<(usize, Option<char>, Option<char>)>,
+    }
+
+    impl Write for Comparer<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for c in s.chars() {
+                let expected = self.expected[self.offset..].chars().next();
+
+                if expected != Some(c) {
+                    self.difference = Some((self.offset, Some(c), expected));
+                    // stops the rendering, since the rest of it doesn't matter
+                    return Err(fmt::Error);
+                }
+
+                self.offset += c.len_utf8();
+            }
+
+            Ok(())
+        }
+    }
+
+    let mut comparer = Comparer {
+        expected,
+        offset: 0,
+        difference: None,
+    };
+
+    if write!(comparer, "{}", inner).is_err() && comparer.difference.is_none() {
+        panic!("a Display implementation returned an error unexpectedly");
+    }
+
+    comparer.difference.or_else(|| {
+        expected[comparer.offset..]
+            .chars()
+            .next()
+            .map(|expected| (comparer.offset, None, Some(expected)))
+    })
+}
+
+/// Repeatedly replace `needle` with `replacement`, feeding the output of each pass into the next
+/// one, until a pass doesn't change anything or `max_iters` passes have been made.
+///
+/// This always terminates after at most `max_iters` passes, which matters when the replacement
+/// contains the needle: every pass then reintroduces it, so the text grows without ever becoming
+/// stable. For example, replacing `"x"` with `"xx"` doubles the length of the text on each pass.
+pub fn replace_to_fixed_point(
+    haystack: &str,
+    needle: &str,
+    replacement: &str,
+    max_iters: usize,
+) -> String {
+    let mut current = haystack.to_string();
+
+    for _ in 0..max_iters {
+        if !current.contains(needle) {
+            break;
+        }
+
+        let next = current.lazy_replace(needle, replacement).to_string();
+        if next == current {
+            break;
+        }
+
+        current = next;
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        detokenize, replace_to_fixed_point, ReplacePreview, ANNOTATION_END, ANNOTATION_START,
+    };
+    use crate::{needle_set, LazyReplace, LazyReplaceDisplay, StaticNeedleSet, VerifyError};
+    use std::{cell::Cell, collections::HashMap, hash::Hasher};
+
+    #[test]
+    fn render_diff() {
+        assert_eq!(
+            "a [-cat-]{+dog+} and a [-cat-]{+dog+}.",
+            "a cat and a cat.".render_diff("cat", "dog")
+        );
+        assert_eq!("no change", "no change".render_diff("cat", "dog"));
+        assert_eq!("[-x-]{++}", "x".render_diff('x', ""));
+    }
+
+    #[test]
+    fn render_annotated() {
+        let annotated = "a cat and a cat.".render_annotated("cat", "dog");
+
+        assert_eq!(
+            "a \u{2068}dog\u{2069} and a \u{2068}dog\u{2069}.",
+            annotated
+        );
+        assert_eq!(
+            "a cat and a cat.".lazy_replace("cat", "dog").to_string(),
+            annotated.replace([ANNOTATION_START, ANNOTATION_END], "")
+        );
+        assert_eq!("x\u{2068}\u{2069}x", "x-x".render_annotated('-', ""));
+        assert_eq!("none", "none".render_annotated('-', ""));
+    }
+
+    #[test]
+    fn preview() {
+        assert_eq!(
+            vec![
+                ReplacePreview {
+                    range: 4..9,
+                    original: "color",
+                    replacement: "colour".to_string(),
+                },
+                ReplacePreview {
+                    range: 15..20,
+                    original: "Color",
+                    replacement: "colour".to_string(),
+                },
+            ],
+            "Set color, not Color.".preview(&needle_set!["color", "Color"], "colour")
+        );
+        assert_eq!(
+            vec![ReplacePreview {
+                range: 1..3,
+                original: "é",
+                replacement: "e".to_string(),
+            }],
+            "cé".preview('é', 'e')
+        );
+        assert!("none".preview('x', 'y').is_empty());
+    }
+
+    /// 64-bit FNV-1a, which hashes one byte at a time, so its output doesn't depend on how the
+    /// input is split across calls to `write`
+    struct Fnv(u64);
+
+    impl Default for Fnv {
+        fn default() -> Self {
+            Fnv(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    impl Hasher for Fnv {
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn digest() {
+        let digest_of = |bytes: &[u8]| {
+            let mut hasher = Fnv::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+
+        // the output is written in many small pieces, which the hasher has to put back together
+        let replaced = "a-b-c-d-e-f-g-h-i-j-k".lazy_replace('-', "--");
+        assert_eq!(
+            digest_of(replaced.to_string().as_bytes()),
+            replaced.digest(&mut Fnv::default())
+        );
+
+        let nested = "x y z".lazy_replace(' ', "a b".lazy_replace(' ', "é"));
+        assert_eq!("xaébyaébz", nested.to_string());
+        assert_eq!(
+            digest_of(nested.to_string().as_bytes()),
+            nested.digest(&mut Fnv::default())
+        );
+        assert_ne!(digest_of(b"x y z"), nested.digest(&mut Fnv::default()));
+    }
+
+    #[test]
+    fn render_verified() {
+        assert_eq!(
+            Ok("name = x, alias = x".to_string()),
+            "name = NAME, alias = ALIAS".render_verified(&needle_set!["NAME", "ALIAS"], 'x', 2)
+        );
+        assert_eq!(Ok("a_b".to_string()), "a.b".render_verified('.', '_', 1));
+        assert_eq!(
+            Err(VerifyError {
+                expected: 1,
+                actual: 2
+            }),
+            "a.b.c".render_verified('.', '_', 1)
+        );
+        assert_eq!(
+            Err(VerifyError {
+                expected: 1,
+                actual: 0
+            }),
+            "abc".render_verified('.', '_', 1)
+        );
+        assert_eq!(Ok("abc".to_string()), "abc".render_verified('.', '_', 0));
+    }
+
+    #[test]
+    fn render_try_with() {
+        let (out, errors) = "1 + 22 + x3y + 300".render_try_with(
+            &needle_set!["1", "22", "x3y", "300"],
+            |matched: &str| match matched.parse::<u8>() {
+                Ok(n) => Ok(n * 2),
+                Err(error) => Err(error.to_string()),
+            },
+        );
+
+        assert_eq!("2 + 44 + x3y + 300", out);
+        assert_eq!(
+            vec![
+                (9..12, "invalid digit found in string".to_string()),
+                (15..18, "number too large to fit in target type".to_string()),
+            ],
+            errors
+        );
+
+        let (out, errors) = "none".render_try_with("x", |_: &str| Err::<&str, ()>(()));
+        assert_eq!("none", out);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn render_tokenized() {
+        let text = "Hello {name}, you have {count} new {count_label}.";
+        let (tokenized, originals) =
+            text.render_tokenized(&needle_set!["{name}", "{count}", "{count_label}"]);
+
+        assert_eq!(
+            "Hello \u{FFFC}0\u{FFFC}, you have \u{FFFC}1\u{FFFC} new \u{FFFC}2\u{FFFC}.",
+            tokenized
+        );
+        assert_eq!(vec!["{name}", "{count}", "{count_label}"], originals);
+        assert_eq!(text, detokenize(&tokenized, &originals));
+
+        let edited = tokenized.replace("Hello", "Hallo");
+        assert_eq!(
+            "Hallo {name}, you have {count} new {count_label}.",
+            detokenize(&edited, &originals)
+        );
+    }
+
+    #[test]
+    fn detokenize_leaves_broken_tokens() {
+        let originals = vec!["x".to_string()];
+
+        assert_eq!(
+            "\u{FFFC}7\u{FFFC} \u{FFFC}ax \u{FFFC}",
+            detokenize(
+                "\u{FFFC}7\u{FFFC} \u{FFFC}a\u{FFFC}0\u{FFFC} \u{FFFC}",
+                &originals
+            )
+        );
+        assert_eq!("", detokenize("", &originals));
+    }
+
+    #[test]
+    fn fixed_point() {
+        assert_eq!("a b", replace_to_fixed_point("a     b", "  ", " ", 100));
+        assert_eq!("ab", replace_to_fixed_point("aaab", "aa", "a", 100));
+        assert_eq!("aab", replace_to_fixed_point("aaaab", "aa", "a", 1));
+        assert_eq!(
+            "unchanged",
+            replace_to_fixed_point("unchanged", "x", "y", 100)
+        );
+        assert_eq!("x", replace_to_fixed_point("x", "x", "x", 100));
+    }
+
+    #[test]
+    fn fixed_point_stops_at_the_cap() {
+        assert_eq!("xxxxxxxx", replace_to_fixed_point("x", "x", "xx", 3));
+        assert_eq!("x", replace_to_fixed_point("x", "x", "xx", 0));
+    }
+
+    #[test]
+    fn render_context() {
+        assert_eq!(
+            vec!["one dog, ", "two dogs"],
+            "one cat, two cats".render_context("cat", "dog", 4, 2)
+        );
+        assert_eq!(vec!["a_bX", "Xb_c"], "aXbXc".render_context('X', '_', 2, 2));
+        assert_eq!(vec!["ä_ö"], "xä→öx".render_context('→', '_', 1, 1));
+        assert_eq!(vec!["_"], "→".render_context('→', '_', 5, 5));
+        assert!("none".render_context('→', '_', 5, 5).is_empty());
+    }
+
+    #[test]
+    fn render_both() {
+        let checked = Cell::new(0);
+        let is_vowel = |c: char| {
+            checked.set(checked.get() + 1);
+            "aeiou".contains(c)
+        };
+
+        assert_eq!(
+            ("h_ll_ w_rld".to_string(), "h*ll* w*rld".to_string()),
+            "hello world".render_both(is_vowel, '_', '*')
+        );
+        assert_eq!("hello world".chars().count(), checked.get());
+    }
+
+    #[test]
+    fn render_with_frequency() {
+        static WORDS: StaticNeedleSet = needle_set!["cat", "cats", "dog"];
+
+        let (out, frequency) =
+            "cats, dog, cat, cats and a dog".render_with_frequency(&WORDS, "pet");
+        assert_eq!("pet, pet, pet, pet and a pet", out);
+        assert_eq!(
+            vec![("cat", 1), ("cats", 2), ("dog", 2)]
+                .into_iter()
+                .map(|(matched, count)| (matched.to_string(), count))
+                .collect::<HashMap<_, _>>(),
+            frequency
+        );
+
+        let (out, frequency) = "a1b22c1".render_with_frequency(|c: char| c.is_ascii_digit(), '#');
+        assert_eq!("a#b##c#", out);
+        assert_eq!(Some(&2), frequency.get("1"));
+        assert_eq!(Some(&2), frequency.get("2"));
+        assert_eq!(2, frequency.len());
+
+        let (out, frequency) = "none".render_with_frequency('x', '_');
+        assert_eq!("none", out);
+        assert!(frequency.is_empty());
+    }
+
+    #[test]
+    fn render_aligned() {
+        assert_eq!(
+            "name     |size  |kind\n\
+             main.rs  |1 KiB |file\n\
+             src      |-     |dir\n\
+             élan.txt |10 B",
+            "name|size|kind\nmain.rs|1 KiB|file\nsrc|-|dir\nélan.txt|10 B"
+                .lazy_replace('|', " |")
+                .render_aligned('\n', '|')
+        );
+        assert_eq!("", "".render_aligned('\n', '|'));
+    }
+
+    #[test]
+    fn collect_into() {
+        let replaced = "a cat, a hat".lazy_replace("at", "öt");
+
+        assert_eq!("a cöt, a höt", replaced.collect_into::<String>());
+        assert_eq!(
+            "a cöt, a höt".chars().collect::<Vec<_>>(),
+            replaced.collect_into::<Vec<char>>()
+        );
+        assert!(""
+            .lazy_replace("at", "öt")
+            .collect_into::<Vec<char>>()
+            .is_empty());
+    }
+
+    #[test]
+    fn diff_against() {
+        let replaced = "a cat and a cat".lazy_replace("cat", "dög");
+
+        assert_eq!(None, replaced.diff_against("a dög and a dög"));
+        assert_eq!(
+            Some((7, Some('a'), Some('o'))),
+            replaced.diff_against("a dög onde")
+        );
+        assert_eq!(Some((8, Some('n'), None)), replaced.diff_against("a dög a"));
+        assert_eq!(
+            Some((17, None, Some('!'))),
+            replaced.diff_against("a dög and a dög!")
+        );
+        assert_eq!(None, "".lazy_replace("x", "y").diff_against(""));
+    }
+}
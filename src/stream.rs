@@ -0,0 +1,154 @@
+//! Finding a needle in a stream of chunks, where a match can be split across several of them.
+
+/// A piece of output produced by a [`StreamMatcher`]
+pub(crate) enum Piece<'c> {
+    /// Bytes that didn't turn out to be part of a match, to be written as they are
+    Literal(&'c [u8]),
+    /// A whole match, to be replaced
+    Match,
+}
+
+/// Splits a stream of chunks into literal bytes and matches of a needle.
+///
+/// The only state carried between chunks is how much of the needle the end of the stream so far
+/// could be the start of. Since those bytes are always a prefix of the needle, they don't need to
+/// be stored: once it is clear that they aren't part of a match, they are written out from the
+/// needle itself.
+///
+/// When the needle is valid UTF-8 and the chunks form valid UTF-8 when joined, every literal piece
+/// is valid UTF-8 too, as pieces are only ever split where the needle could start.
+///
+/// An empty needle never matches.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StreamMatcher {
+    partial: usize,
+}
+
+impl StreamMatcher {
+    /// Feed the next chunk of the stream through, passing each finished piece to `emit`
+    pub(crate) fn feed<E>(
+        &mut self,
+        needle: &[u8],
+        chunk: &[u8],
+        mut emit: impl FnMut(Piece<'_>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if needle.is_empty() {
+            return emit(Piece::Literal(chunk));
+        }
+
+        let mut rest = chunk;
+
+        while self.partial > 0 {
+            let wanted = &needle[self.partial..];
+            let available = wanted.len().min(rest.len());
+
+            if wanted[..available] == rest[..available] {
+                if available < wanted.len() {
+                    self.partial += available;
+                    return Ok(());
+                }
+
+                emit(Piece::Match)?;
+                self.partial = 0;
+                rest = &rest[available..];
+            } else {
+                // drop the shortest start of the held-back bytes that leaves a prefix of the
+                // needle behind, and try again with that
+                let held = &needle[..self.partial];
+                let shift = (1..=self.partial)
+                    .find(|&shift| needle.starts_with(&held[shift..]))
+                    .expect("an empty remainder is always a prefix");
+
+                emit(Piece::Literal(&needle[..shift]))?;
+                self.partial -= shift;
+            }
+        }
+
+        while let Some(start) = find(rest, needle) {
+            emit(Piece::Literal(&rest[..start]))?;
+            emit(Piece::Match)?;
+            rest = &rest[start + needle.len()..];
+        }
+
+        // hold back the longest end of the chunk that could be the start of a match
+        let earliest = rest.len().saturating_sub(needle.len() - 1);
+        let held_from = (earliest..rest.len())
+            .find(|&i| needle.starts_with(&rest[i..]))
+            .unwrap_or(rest.len());
+
+        self.partial = rest.len() - held_from;
+        emit(Piece::Literal(&rest[..held_from]))
+    }
+
+    /// End the stream, writing out anything that was held back as the possible start of a match
+    pub(crate) fn finish<E>(
+        &mut self,
+        needle: &[u8],
+        mut emit: impl FnMut(Piece<'_>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let held = self.partial;
+        self.partial = 0;
+
+        if held > 0 {
+            emit(Piece::Literal(&needle[..held]))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The start of the first occurrence of `needle`, which must not be empty, in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+
+    while let Some(i) = memchr::memchr(needle[0], &haystack[offset..]) {
+        let start = offset + i;
+
+        if haystack[start..].starts_with(needle) {
+            return Some(start);
+        }
+
+        offset = start + 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Piece, StreamMatcher};
+    use std::convert::Infallible;
+
+    fn replace_chunked(needle: &str, chunks: &[&str]) -> String {
+        let mut matcher = StreamMatcher::default();
+        let mut out = Vec::new();
+        let mut emit = |piece: Piece<'_>| {
+            match piece {
+                Piece::Literal(bytes) => out.extend_from_slice(bytes),
+                Piece::Match => out.push(b'_'),
+            }
+            Ok::<_, Infallible>(())
+        };
+
+        for chunk in chunks {
+            matcher
+                .feed(needle.as_bytes(), chunk.as_bytes(), &mut emit)
+                .unwrap();
+        }
+        matcher.finish(needle.as_bytes(), &mut emit).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn matches_across_chunks() {
+        assert_eq!("a_b", replace_chunked("xyz", &["ax", "y", "zb"]));
+        assert_eq!("a_b_", replace_chunked("xyz", &["axyzbx", "yz"]));
+        assert_eq!("xxy", replace_chunked("xyz", &["x", "x", "y"]));
+        assert_eq!("aa_", replace_chunked("aab", &["aa", "aab"]));
+        assert_eq!("_ab", replace_chunked("abab", &["ab", "ab", "ab"]));
+        assert_eq!("a_", replace_chunked("abab", &["aab", "a", "b"]));
+        assert_eq!("_é", replace_chunked("éé", &["é", "é", "é"]));
+        assert_eq!("unchanged", replace_chunked("", &["unch", "anged"]));
+    }
+}
@@ -0,0 +1,429 @@
+//! Replacements that transform the matched text itself, such as by decoding or re-encoding it.
+
+use crate::{
+    pattern::{Pattern, Searcher},
+    write_matches,
+};
+use std::{
+    fmt::{self, Write},
+    ops::Range,
+    str,
+};
+
+/// A lazily-replaced string where each match is replaced with its percent-decoded form, so `%20`
+/// becomes a space.
+///
+/// A `%` that isn't followed by two hex digits is left as it is. So are the escapes of decoded
+/// bytes that don't form valid UTF-8, like `%FF`, or a `%C3` that isn't followed by the rest of
+/// its character.
+pub struct ReplacedPercentDecode<'a, P> {
+    haystack: &'a str,
+    needle: P,
+}
+
+impl<'a, P> ReplacedPercentDecode<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P) -> Self {
+        ReplacedPercentDecode { haystack, needle }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedPercentDecode<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            let matched = &self.haystack[range];
+            let bytes = matched.as_bytes();
+            let mut decoder = Utf8Decoder::default();
+            let mut literal_start = 0;
+            let mut i = 0;
+
+            while i < bytes.len() {
+                let escape = match bytes.get(i..i + 3) {
+                    Some(&[b'%', high, low]) => {
+                        hex_value(high).and_then(|high| hex_value(low).map(|low| high << 4 | low))
+                    }
+                    _ => None,
+                };
+
+                match escape {
+                    Some(byte) => {
+                        if literal_start < i {
+                            decoder.flush(f)?;
+                            f.write_str(&matched[literal_start..i])?;
+                        }
+
+                        decoder.push(f, byte, &matched[i..i + 3])?;
+                        i += 3;
+                        literal_start = i;
+                    }
+                    // `i` may end up inside a multi-byte character here, but the literal text is
+                    // only sliced at a `%` or the end of the match
+                    None => i += 1,
+                }
+            }
+
+            decoder.flush(f)?;
+            f.write_str(&matched[literal_start..])
+        })
+    }
+}
+
+/// A lazily-replaced string where each run of digits matched by the needle has `sep` inserted
+/// between each group of three digits, counted from the right, so `1234567` becomes `1,234,567`.
+///
+/// Matches that directly follow each other are grouped as one number, so a `char` predicate like
+/// `|c: char| c.is_ascii_digit()` can be used to match whole runs of digits. The digits are copied
+/// as they are, so leading zeros are kept and grouped like any other digit, and numbers of any
+/// length work, since they are never parsed into an integer. A run that contains anything other
+/// than ASCII digits is left as it is.
+pub struct ReplacedGroupDigits<'a, P> {
+    haystack: &'a str,
+    needle: P,
+    sep: char,
+}
+
+impl<'a, P> ReplacedGroupDigits<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, sep: char) -> Self {
+        ReplacedGroupDigits {
+            haystack,
+            needle,
+            sep,
+        }
+    }
+
+    fn write_grouped(&self, f: &mut fmt::Formatter, digits: &str) -> fmt::Result {
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return f.write_str(digits);
+        }
+
+        // the first group is the only one that may be shorter than three digits
+        let (first, rest) = digits.split_at((digits.len() - 1) % 3 + 1);
+        f.write_str(first)?;
+
+        for group in rest.as_bytes().chunks(3) {
+            f.write_char(self.sep)?;
+            f.write_str(str::from_utf8(group).expect("digits are ASCII"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedGroupDigits<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut searcher = self.needle.clone().into_searcher(self.haystack);
+        let mut position = 0;
+        let mut run: Option<Range<usize>> = None;
+
+        while let Some((start, end)) = searcher.next_match() {
+            match run {
+                Some(ref mut run) if run.end == start => run.end = end,
+                _ => {
+                    if let Some(run) = run.replace(start..end) {
+                        f.write_str(&self.haystack[position..run.start])?;
+                        self.write_grouped(f, &self.haystack[run.clone()])?;
+                        position = run.end;
+                    }
+                }
+            }
+        }
+
+        if let Some(run) = run {
+            f.write_str(&self.haystack[position..run.start])?;
+            self.write_grouped(f, &self.haystack[run.clone()])?;
+            position = run.end;
+        }
+
+        f.write_str(&self.haystack[position..])
+    }
+}
+
+/// The code words of the NATO phonetic alphabet, from `A` to `Z`
+const NATO_ALPHABET: [&str; 26] = [
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett",
+    "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango",
+    "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
+];
+
+/// A lazily-replaced string where each match is spelled out in the NATO phonetic alphabet, so
+/// `SOS` becomes `Sierra Oscar Sierra`.
+///
+/// Each character of the match is replaced with its code word if it is an ASCII letter, in either
+/// case, or written as it is otherwise, and these are separated by single spaces. `A1` becomes
+/// `Alfa 1`, for example.
+pub struct ReplacedNato<'a, P> {
+    haystack: &'a str,
+    needle: P,
+}
+
+impl<'a, P> ReplacedNato<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P) -> Self {
+        ReplacedNato { haystack, needle }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedNato<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            for (i, c) in self.haystack[range].chars().enumerate() {
+                if i > 0 {
+                    f.write_char(' ')?;
+                }
+
+                if c.is_ascii_alphabetic() {
+                    f.write_str(NATO_ALPHABET[(c.to_ascii_uppercase() as u8 - b'A') as usize])?;
+                } else {
+                    f.write_char(c)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// The encodings [`ReplacedEncode`] can encode matches with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Lowercase hexadecimal, with two digits per byte
+    Hex,
+    /// Base64 with the standard alphabet from RFC 4648, padded with `=`
+    Base64,
+}
+
+/// The alphabet of [`Encoding::Base64`]
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A lazily-replaced string where each match is replaced with an encoding of its UTF-8 bytes, such
+/// as hex or base64.
+pub struct ReplacedEncode<'a, P> {
+    haystack: &'a str,
+    needle: P,
+    encoding: Encoding,
+}
+
+impl<'a, P> ReplacedEncode<'a, P> {
+    /// Create a new instance of this type
+    pub fn new(haystack: &'a str, needle: P, encoding: Encoding) -> Self {
+        ReplacedEncode {
+            haystack,
+            needle,
+            encoding,
+        }
+    }
+}
+
+impl<'a, P> fmt::Display for ReplacedEncode<'a, P>
+where
+    P: Pattern<'a> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_matches(f, self.haystack, self.needle.clone(), |f, _, range| {
+            let bytes = self.haystack[range].as_bytes();
+
+            match self.encoding {
+                Encoding::Hex => bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte)),
+                Encoding::Base64 => bytes.chunks(3).try_for_each(|chunk| {
+                    let mut group = [0; 3];
+                    group[..chunk.len()].copy_from_slice(chunk);
+                    let bits =
+                        u32::from(group[0]) << 16 | u32::from(group[1]) << 8 | u32::from(group[2]);
+
+                    // a chunk of n bytes is encoded as n + 1 characters, and padded to four
+                    for i in 0..4 {
+                        if i <= chunk.len() {
+                            let index = (bits >> (18 - 6 * i)) & 0x3f;
+                            f.write_char(BASE64_ALPHABET[index as usize] as char)?;
+                        } else {
+                            f.write_char('=')?;
+                        }
+                    }
+
+                    Ok(())
+                }),
+            }
+        })
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+/// Collects decoded bytes until they form a whole character, so they can be written without an
+/// intermediate allocation. Bytes that can't be part of a valid character are written as the
+/// escapes they were decoded from.
+#[derive(Default)]
+struct Utf8Decoder<'m> {
+    buffer: [u8; 4],
+    /// The escape each byte in `buffer` was decoded from
+    escapes: [&'m str; 4],
+    len: usize,
+}
+
+impl<'m> Utf8Decoder<'m> {
+    fn push<W: Write + ?Sized>(&mut self, w: &mut W, byte: u8, escape: &'m str) -> fmt::Result {
+        self.buffer[self.len] = byte;
+        self.escapes[self.len] = escape;
+        self.len += 1;
+
+        while self.len > 0 {
+            match str::from_utf8(&self.buffer[..self.len]) {
+                Ok(s) => {
+                    w.write_str(s)?;
+                    self.len = 0;
+                }
+                Err(e) => match e.error_len() {
+                    // an incomplete character, which later bytes may finish
+                    None => break,
+                    Some(invalid) => {
+                        self.escapes[..invalid]
+                            .iter()
+                            .try_for_each(|escape| w.write_str(escape))?;
+                        self.buffer.copy_within(invalid..self.len, 0);
+                        self.escapes.copy_within(invalid..self.len, 0);
+                        self.len -= invalid;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out the escapes of an incomplete character, which can't be finished anymore
+    fn flush<W: Write + ?Sized>(&mut self, w: &mut W) -> fmt::Result {
+        self.escapes[..self.len]
+            .iter()
+            .try_for_each(|escape| w.write_str(escape))?;
+        self.len = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{needle_set, Encoding, LazyReplace, StaticNeedleSet};
+
+    static ESCAPES: StaticNeedleSet = needle_set!["%20", "%C3%A9", "%2", "%FF", "a%41%2"];
+
+    #[test]
+    fn replace_percent_decode() {
+        assert_eq!(
+            "hello world, café",
+            "hello%20world, caf%C3%A9"
+                .lazy_replace_percent_decode(&ESCAPES)
+                .to_string()
+        );
+        assert_eq!(
+            "50%2 %FF aA%2",
+            "50%2 %FF a%41%2"
+                .lazy_replace_percent_decode(&ESCAPES)
+                .to_string()
+        );
+        assert_eq!(
+            "a b",
+            "a%20b".lazy_replace_percent_decode("%20").to_string()
+        );
+    }
+
+    #[test]
+    fn percent_decode_malformed_utf8() {
+        let decode = |s: &str| s.lazy_replace_percent_decode(s).to_string();
+
+        assert_eq!("A%FF", decode("%41%FF"));
+        assert_eq!("%C3", decode("%C3"));
+        assert_eq!("%c3 ", decode("%c3%20"));
+        assert_eq!("%C3A", decode("%C3%41"));
+        assert_eq!("%E2%82 é", decode("%E2%82%20%C3%A9"));
+    }
+
+    #[test]
+    fn replace_group_digits() {
+        let is_digit = |c: char| c.is_ascii_digit();
+
+        assert_eq!(
+            "1,234,567",
+            "1234567"
+                .lazy_replace_group_digits(is_digit, ',')
+                .to_string()
+        );
+        assert_eq!(
+            "pop. 8 000 000 000 (2 022), 0 012 345 km, 1 to 12 or 123",
+            "pop. 8000000000 (2022), 0012345 km, 1 to 12 or 123"
+                .lazy_replace_group_digits(is_digit, ' ')
+                .to_string()
+        );
+        assert_eq!(
+            format!("1{}", ",000".repeat(30)),
+            format!("1{}", "000".repeat(30))
+                .lazy_replace_group_digits(is_digit, ',')
+                .to_string()
+        );
+        assert_eq!(
+            "12,345 and 12a45",
+            "12345 and 12a45"
+                .lazy_replace_group_digits("12345", ',')
+                .to_string()
+        );
+        assert_eq!(
+            "12a45",
+            "12a45"
+                .lazy_replace_group_digits(|c: char| c.is_ascii_alphanumeric(), ',')
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn replace_nato() {
+        assert_eq!(
+            "send Sierra Oscar Sierra now",
+            "send SOS now".lazy_replace_nato("SOS").to_string()
+        );
+        assert_eq!(
+            "call sign Delta Lima 4 0 2, cleared",
+            "call sign DL402, cleared"
+                .lazy_replace_nato("DL402")
+                .to_string()
+        );
+        assert_eq!(
+            "Juliett - X-ray é",
+            "j-x é".lazy_replace_nato("j-x").to_string()
+        );
+    }
+
+    #[test]
+    fn replace_encode() {
+        static WORDS: StaticNeedleSet = needle_set!["f", "foob", "fooba", "foobar"];
+
+        assert_eq!(
+            "key=736563726574",
+            "key=secret"
+                .lazy_replace_encode("secret", Encoding::Hex)
+                .to_string()
+        );
+        assert_eq!(
+            "c3a9",
+            "é".lazy_replace_encode('é', Encoding::Hex).to_string()
+        );
+        assert_eq!(
+            "Zm9vYmFy Zm9vYg== Zm9vYmE= Zg==",
+            "foobar foob fooba f"
+                .lazy_replace_encode(&WORDS, Encoding::Base64)
+                .to_string()
+        );
+    }
+}